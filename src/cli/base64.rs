@@ -1,8 +1,11 @@
-use std::str::FromStr;
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
 
 use crate::{
-    process::base64::{process_decode, process_encode},
-    utils::open_reader,
+    process::base64::{process_decode, process_decode_auto, process_encode, LineWrapper},
+    utils::{open_reader, open_writer},
     CmdExector,
 };
 
@@ -15,9 +18,15 @@ use std::fmt::Display;
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum Base64SubCommand {
-    #[command(name = "encode", about = "Encode a string to base64")]
+    #[command(
+        name = "encode",
+        about = "Encode data to base64, base32, base58, base85 or hex"
+    )]
     Encode(Base64EncodeOpts),
-    #[command(name = "decode", about = "Decode a base64 string")]
+    #[command(
+        name = "decode",
+        about = "Decode base64, base32, base58, base85 or hex data"
+    )]
     Decode(Base64DecodeOpts),
 }
 
@@ -25,29 +34,47 @@ pub enum Base64SubCommand {
 pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
-    #[arg(short, long, default_value_t = Base64Method::UrlSafe)]
-    pub method: Base64Method,
+    #[arg(short, long, default_value_t = EncodingMethod::UrlSafe)]
+    pub method: EncodingMethod,
+    /// Wrap output lines at this many characters (76 for MIME), 0 to disable
+    #[arg(short, long, default_value_t = 0)]
+    pub wrap: usize,
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
-    #[arg(short, long, default_value_t = Base64Method::UrlSafe)]
-    pub method: Base64Method,
+    /// File for the decoded bytes, "-" for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(short, long, default_value_t = EncodingMethod::UrlSafe)]
+    pub method: EncodingMethod,
+    /// Detect the alphabet and padding, and report what the decoded data looks like
+    #[arg(long, conflicts_with = "method")]
+    pub auto: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Base64Method {
-    UrlSafe,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingMethod {
     Standard,
+    UrlSafe,
+    StandardNoPad,
+    UrlSafeNoPad,
+    Base32,
+    Base32NoPad,
+    Base32Hex,
+    Base58,
+    Base85,
+    Hex,
 }
 
 impl CmdExector for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let mut reader = open_reader(&self.input)?;
-        let ret = process_encode(reader.as_mut(), self.method)?;
-        println!("{}", ret);
+        let mut writer = LineWrapper::new(io::stdout().lock(), self.wrap);
+        process_encode(reader.as_mut(), &mut writer, self.method)?;
+        writeln!(writer)?;
         Ok(())
     }
 }
@@ -55,34 +82,62 @@ impl CmdExector for Base64EncodeOpts {
 impl CmdExector for Base64DecodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let mut reader = open_reader(&self.input)?;
-        let ret = process_decode(reader.as_mut(), self.method)?;
-        println!("{}", ret);
+        let mut writer = open_writer(&self.output)?;
+        if self.auto {
+            let ret = process_decode_auto(reader.as_mut(), writer.as_mut())?;
+            eprintln!("Detected encoding: {}", ret.method);
+            if ret.content.is_empty() {
+                eprintln!("Content: unknown binary data");
+            }
+            for content in ret.content {
+                eprintln!("Content: {} ({})", content.description, content.mime);
+            }
+        } else {
+            process_decode(reader.as_mut(), writer.as_mut(), self.method)?;
+        }
+        writer.flush()?;
         Ok(())
     }
 }
 
-impl FromStr for Base64Method {
+impl FromStr for EncodingMethod {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "urlsafe" => Ok(Base64Method::UrlSafe),
-            "standard" => Ok(Base64Method::Standard),
-            _ => Err(anyhow::anyhow!("Invalid base64 method: {}", s)),
+            "urlsafe" => Ok(EncodingMethod::UrlSafe),
+            "standard" => Ok(EncodingMethod::Standard),
+            "urlsafe-nopad" => Ok(EncodingMethod::UrlSafeNoPad),
+            "standard-nopad" => Ok(EncodingMethod::StandardNoPad),
+            "base32" => Ok(EncodingMethod::Base32),
+            "base32-nopad" => Ok(EncodingMethod::Base32NoPad),
+            "base32hex" => Ok(EncodingMethod::Base32Hex),
+            "base58" => Ok(EncodingMethod::Base58),
+            "base85" | "ascii85" => Ok(EncodingMethod::Base85),
+            "hex" => Ok(EncodingMethod::Hex),
+            _ => Err(anyhow::anyhow!("Invalid encoding method: {}", s)),
         }
     }
 }
 
-impl From<Base64Method> for &'static str {
-    fn from(method: Base64Method) -> Self {
+impl From<EncodingMethod> for &'static str {
+    fn from(method: EncodingMethod) -> Self {
         match method {
-            Base64Method::UrlSafe => "urlsafe",
-            Base64Method::Standard => "standard",
+            EncodingMethod::UrlSafe => "urlsafe",
+            EncodingMethod::Standard => "standard",
+            EncodingMethod::UrlSafeNoPad => "urlsafe-nopad",
+            EncodingMethod::StandardNoPad => "standard-nopad",
+            EncodingMethod::Base32 => "base32",
+            EncodingMethod::Base32NoPad => "base32-nopad",
+            EncodingMethod::Base32Hex => "base32hex",
+            EncodingMethod::Base58 => "base58",
+            EncodingMethod::Base85 => "base85",
+            EncodingMethod::Hex => "hex",
         }
     }
 }
 
-impl Display for Base64Method {
+impl Display for EncodingMethod {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", Into::<&str>::into(*self))
     }
@@ -0,0 +1,60 @@
+use std::io::Write;
+
+use anyhow::Ok;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use super::verify_file;
+use crate::{
+    process::datauri::{process_datauri_decode, process_datauri_encode},
+    utils::{open_writer, read_content},
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum DataUriSubCommand {
+    #[command(about = "Turn a file into a data:<mime>;base64,... URI")]
+    Encode(DataUriEncodeOpts),
+    #[command(about = "Parse a data URI back into the original bytes")]
+    Decode(DataUriDecodeOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct DataUriEncodeOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+    /// MIME type to use instead of detecting it from the content or extension
+    #[arg(short, long)]
+    pub mime: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct DataUriDecodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+}
+
+impl CmdExector for DataUriEncodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let data = read_content(&self.input)?;
+        let filename = (self.input != "-").then_some(self.input.as_str());
+        let uri = process_datauri_encode(&data, filename, self.mime.as_deref())?;
+        println!("{}", uri);
+        Ok(())
+    }
+}
+
+impl CmdExector for DataUriDecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let uri = String::from_utf8(read_content(&self.input)?)?;
+        let parsed = process_datauri_decode(&uri)?;
+        eprintln!("MIME type: {}", parsed.mime);
+        let mut writer = open_writer(&self.output)?;
+        writer.write_all(&parsed.data)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
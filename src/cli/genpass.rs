@@ -1,11 +1,32 @@
 use anyhow::Ok;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use zxcvbn::zxcvbn;
 
-use crate::{process::genpass::process_genpass, CmdExector};
+use super::verify_file;
+use crate::{
+    process::genpass::{process_genpass, process_genpass_derive, PasswordPolicy},
+    utils::{read_content, trim_newline},
+    CmdExector,
+};
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
+    #[command(subcommand)]
+    pub sub: Option<GenPassSubCommand>,
+
+    #[command(flatten)]
+    pub policy: GenPassPolicyArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GenPassSubCommand {
+    #[command(about = "Derive a deterministic site password from a master secret")]
+    Derive(GenPassDeriveOpts),
+}
+
+#[derive(Debug, Args)]
+pub struct GenPassPolicyArgs {
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
 
@@ -22,19 +43,73 @@ pub struct GenPassOpts {
     pub lowercase: bool,
 }
 
+#[derive(Debug, Parser)]
+pub struct GenPassDeriveOpts {
+    #[arg(long)]
+    pub site: String,
+
+    #[arg(long)]
+    pub login: String,
+
+    #[arg(long, default_value_t = 1)]
+    pub counter: u32,
+
+    /// File holding the master secret, "-" for stdin
+    #[arg(long, value_parser = verify_file, default_value = "-")]
+    pub master: String,
+
+    #[command(flatten)]
+    pub policy: GenPassPolicyArgs,
+}
+
+impl From<&GenPassPolicyArgs> for PasswordPolicy {
+    fn from(args: &GenPassPolicyArgs) -> Self {
+        Self {
+            length: args.length,
+            number: args.number,
+            symbol: args.symbol,
+            uppercase: args.uppercase,
+            lowercase: args.lowercase,
+        }
+    }
+}
+
 impl CmdExector for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        if let Some(GenPassSubCommand::Derive(opts)) = self.sub {
+            return opts.execute().await;
+        }
+
         let password = process_genpass(
-            self.length,
-            self.number,
-            self.symbol,
-            self.uppercase,
-            self.lowercase,
+            self.policy.length,
+            self.policy.number,
+            self.policy.symbol,
+            self.policy.uppercase,
+            self.policy.lowercase,
         )?;
-        println!("{}", password);
+        print_password(&password)
+    }
+}
 
-        let estimate = zxcvbn(&password, &[])?;
-        eprintln!("Estimated strength: {}", estimate.score());
-        Ok(())
+impl CmdExector for GenPassDeriveOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // a secret piped in with echo or stored in a file ends with a newline
+        let master = read_content(&self.master)?;
+        let password = process_genpass_derive(
+            trim_newline(&master),
+            &self.site,
+            &self.login,
+            self.counter,
+            &(&self.policy).into(),
+        )?;
+        print_password(&password)
     }
 }
+
+fn print_password(password: &str) -> anyhow::Result<()> {
+    println!("{}", password);
+
+    let estimate = zxcvbn(password, &[])?;
+    eprintln!("Estimated strength: {}", estimate.score());
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Ok};
+use clap::Parser;
+use std::{fmt::Display, io, str::FromStr};
+
+use crate::{
+    process::hash::{collect_inputs, format_checksum, process_hash_check, process_hash_file},
+    utils::read_content,
+    CmdExector,
+};
+
+use super::verify_file;
+
+// rcli hash -a sha256 src Cargo.toml > SUMS && rcli hash -a sha256 --check SUMS
+#[derive(Debug, Parser)]
+pub struct HashOpts {
+    /// Files or directories (hashed recursively) to hash, "-" for stdin
+    #[arg(default_value = "-")]
+    pub inputs: Vec<String>,
+    #[arg(short, long, default_value_t = HashAlgorithm::Sha256)]
+    pub algorithm: HashAlgorithm,
+    /// Print BSD style lines, `SHA256 (file) = digest`, instead of the GNU
+    /// coreutils `digest  file`
+    #[arg(long)]
+    pub tag: bool,
+    /// Check the files listed in a checksum file, GNU or BSD style; -a is
+    /// only used for GNU lines
+    #[arg(short, long, value_parser = verify_file, conflicts_with_all = ["inputs", "tag"])]
+    pub check: Option<String>,
+    /// Don't print OK for each verified file
+    #[arg(short, long, requires = "check")]
+    pub quiet: bool,
+    /// Don't fail or report on files that are missing
+    #[arg(long, requires = "check")]
+    pub ignore_missing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Blake3,
+    Sha256,
+    Sha512,
+    Sha3_256,
+    Sha3_512,
+}
+
+impl CmdExector for HashOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(sums) = &self.check {
+            let sums = String::from_utf8(read_content(sums)?)?;
+            let summary = process_hash_check(
+                &sums,
+                self.algorithm,
+                self.ignore_missing,
+                self.quiet,
+                &mut io::stdout(),
+            )?;
+            for warning in summary.warnings() {
+                eprintln!("WARNING: {}", warning);
+            }
+            if !summary.passed() {
+                return Err(anyhow!("checksum verification failed"));
+            }
+            return Ok(());
+        }
+
+        for input in collect_inputs(&self.inputs)? {
+            let digest = process_hash_file(&input, self.algorithm)?;
+            println!(
+                "{}",
+                format_checksum(&digest, &input, self.algorithm, self.tag)
+            );
+        }
+        Ok(())
+    }
+}
+
+impl HashAlgorithm {
+    /// Digest size in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            HashAlgorithm::Blake3 | HashAlgorithm::Sha256 | HashAlgorithm::Sha3_256 => 32,
+            HashAlgorithm::Sha512 | HashAlgorithm::Sha3_512 => 64,
+        }
+    }
+
+    /// Name in BSD style lines, as `cksum --tag` and `shasum --tag` write it.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "BLAKE3",
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Sha512 => "SHA512",
+            HashAlgorithm::Sha3_256 => "SHA3-256",
+            HashAlgorithm::Sha3_512 => "SHA3-512",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Sha3_256,
+            HashAlgorithm::Sha3_512,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.tag() == tag)
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "sha3" | "sha3-256" => Ok(HashAlgorithm::Sha3_256),
+            "sha3-512" => Ok(HashAlgorithm::Sha3_512),
+            _ => Err(anyhow!("Invalid hash algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<HashAlgorithm> for &'static str {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Sha3_256 => "sha3-256",
+            HashAlgorithm::Sha3_512 => "sha3-512",
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -6,7 +6,10 @@ use enum_dispatch::enum_dispatch;
 
 use super::verify_file;
 use crate::{
-    process::jwt::{process_jwt_sign, process_jwt_verify, Claims},
+    process::{
+        jwt::{process_jwt_sign, process_jwt_verify, Claims},
+        keyring::read_keyring_key,
+    },
     utils::read_content,
     CmdExector, ReadableDuration,
 };
@@ -30,6 +33,7 @@ pub struct JwtSignOpts {
     iss: String,
     #[arg(short, long, value_parser = parse_jwt_exp, default_value = "14d")]
     exp: u64,
+    /// Secret, or @name for a key in the keyring
     #[arg(short, long, default_value = "rcli-default-key")]
     key: String,
 }
@@ -41,10 +45,18 @@ fn parse_jwt_exp(s: &str) -> Result<u64, &'static str> {
     CoreResult::Ok(exp)
 }
 
+fn jwt_key(key: &str) -> anyhow::Result<Vec<u8>> {
+    match key.strip_prefix('@') {
+        Some(name) => read_keyring_key(name, None),
+        None => Ok(key.as_bytes().to_vec()),
+    }
+}
+
 #[derive(Debug, Parser)]
 pub struct JwtVerifyOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
+    /// Secret, or @name for a key in the keyring
     #[arg(short, long, default_value = "rcli-default-key")]
     pub key: String,
 }
@@ -57,7 +69,7 @@ impl CmdExector for JwtSignOpts {
             exp: self.exp,
         };
 
-        let sig = process_jwt_sign(&self.key, &claim)?;
+        let sig = process_jwt_sign(&jwt_key(&self.key)?, &claim)?;
         print!("{}", sig);
         Ok(())
     }
@@ -68,7 +80,7 @@ impl CmdExector for JwtVerifyOpts {
         let sig = read_content(&self.input)?;
         let sig = String::from_utf8(sig)?;
         let mut sig = sig.trim().as_bytes();
-        match process_jwt_verify(&mut sig, &self.key) {
+        match process_jwt_verify(&mut sig, &jwt_key(&self.key)?) {
             Err(e) => println!("⚠ JWT verification failed: {}", e),
             _ => println!("✓ JWT verified"),
         };
@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Ok};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, io::Write, str::FromStr};
+
+use super::{text::PassphraseArgs, verify_file};
+use crate::{
+    process::keyring::{format_timestamp, read_keyring_key, Keyring},
+    utils::{current_timestamp, open_writer, read_content, write_private_file},
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum KeySubCommand {
+    #[command(about = "List the keys in the keyring")]
+    List(KeyListOpts),
+    #[command(about = "Add a key to the keyring, optionally encrypted with a passphrase")]
+    Add(KeyAddOpts),
+    #[command(about = "Remove a key from the keyring")]
+    Remove(KeyRemoveOpts),
+    #[command(about = "Show a key's details, or export it")]
+    Show(KeyShowOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct KeyListOpts {}
+
+#[derive(Debug, Parser)]
+pub struct KeyAddOpts {
+    /// Name to refer to the key by, as @name in --key
+    pub name: String,
+    #[arg(short = 't', long = "type")]
+    pub key_type: KeyType,
+    /// Key file, e.g. from `text generate`; "-" for stdin
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// Passphrase to encrypt the key with
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct KeyRemoveOpts {
+    pub name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct KeyShowOpts {
+    pub name: String,
+    /// Write the key, decrypted, to this file ("-" for stdout)
+    #[arg(long)]
+    pub export: Option<String>,
+    /// Passphrase of an encrypted key, prompted for when not given
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+/// What a keyring entry holds; `secret` is any shared secret, such as an
+/// HMAC or JWT key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    Blake3,
+    Ed25519,
+    ChaCha20Poly1305,
+    X25519,
+    Secret,
+}
+
+impl CmdExector for KeyListOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let keyring = Keyring::open_default()?;
+        let entries = keyring.list()?;
+        if entries.is_empty() {
+            eprintln!("No keys in {}", keyring.dir().display());
+            return Ok(());
+        }
+        println!(
+            "{:<20} {:<16} {:<16} {:<20} ENCRYPTED",
+            "NAME", "TYPE", "FINGERPRINT", "CREATED (UTC)"
+        );
+        for entry in entries {
+            println!(
+                "{:<20} {:<16} {:<16} {:<20} {}",
+                entry.name,
+                entry.key_type.to_string(),
+                entry.fingerprint,
+                format_timestamp(entry.created),
+                if entry.encrypted { "yes" } else { "no" }
+            );
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for KeyAddOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let data = read_content(&self.input)?;
+        let passphrase = self.passphrase.read(true)?;
+        let keyring = Keyring::open_default()?;
+        let entry = keyring.add(
+            &self.name,
+            self.key_type,
+            &data,
+            passphrase.as_deref(),
+            current_timestamp()?,
+        )?;
+        println!(
+            "Added {} key @{} ({})",
+            entry.key_type, entry.name, entry.fingerprint
+        );
+        Ok(())
+    }
+}
+
+impl CmdExector for KeyRemoveOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let entry = Keyring::open_default()?.remove(&self.name)?;
+        println!(
+            "Removed {} key @{} ({})",
+            entry.key_type, entry.name, entry.fingerprint
+        );
+        Ok(())
+    }
+}
+
+impl CmdExector for KeyShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(export) = &self.export {
+            let key = read_keyring_key(&self.name, self.passphrase.read(false)?.as_deref())?;
+            if export == "-" {
+                let mut writer = open_writer(export)?;
+                writer.write_all(&key)?;
+                writer.flush()?;
+            } else {
+                write_private_file(export, &key)?;
+            }
+            return Ok(());
+        }
+
+        let entry = Keyring::open_default()?.get(&self.name)?;
+        println!("Name:        {}", entry.name);
+        println!("Type:        {}", entry.key_type);
+        println!("Fingerprint: {}", entry.fingerprint);
+        println!("Created:     {} UTC", format_timestamp(entry.created));
+        println!(
+            "Encrypted:   {}",
+            if entry.encrypted { "yes" } else { "no" }
+        );
+        if let Some(public) = &entry.public {
+            println!("Public key:  {}", public);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(KeyType::Blake3),
+            "ed25519" => Ok(KeyType::Ed25519),
+            "chacha20poly1305" => Ok(KeyType::ChaCha20Poly1305),
+            "x25519" => Ok(KeyType::X25519),
+            "secret" => Ok(KeyType::Secret),
+            _ => Err(anyhow!("Invalid key type: {}", s)),
+        }
+    }
+}
+
+impl From<KeyType> for &'static str {
+    fn from(key_type: KeyType) -> Self {
+        match key_type {
+            KeyType::Blake3 => "blake3",
+            KeyType::Ed25519 => "ed25519",
+            KeyType::ChaCha20Poly1305 => "chacha20poly1305",
+            KeyType::X25519 => "x25519",
+            KeyType::Secret => "secret",
+        }
+    }
+}
+
+impl Display for KeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
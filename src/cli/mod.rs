@@ -1,24 +1,36 @@
 pub mod base64;
 pub mod csv;
+pub mod datauri;
 pub mod genpass;
+pub mod hash;
 pub mod http;
 pub mod jwt;
+pub mod key;
+pub mod otp;
+pub mod secret;
 pub mod text;
+pub mod webhook;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::{process::csv::process_csv, CmdExector};
 
 pub use self::{
     base64::{Base64DecodeOpts, Base64EncodeOpts, Base64SubCommand},
     csv::CsvOpts,
-    genpass::GenPassOpts,
+    datauri::{DataUriDecodeOpts, DataUriEncodeOpts, DataUriSubCommand},
+    genpass::{GenPassDeriveOpts, GenPassOpts, GenPassSubCommand},
+    hash::HashOpts,
     http::{HttpServeOpts, HttpSubCommand},
     jwt::{JwtSignOpts, JwtSubCommand, JwtVerifyOpts},
+    key::{KeyAddOpts, KeyListOpts, KeyRemoveOpts, KeyShowOpts, KeySubCommand},
+    otp::{OtpGenerateOpts, OtpHotpOpts, OtpSubCommand, OtpTotpOpts, OtpUriOpts, OtpVerifyOpts},
+    secret::{SecretCombineOpts, SecretSplitOpts, SecretSubCommand},
     text::{
-        TextDecryptOpts, TextEncryptOpts, TextGenerateOpts, TextSignOpts, TextSubCommand,
-        TextVerifyOpts,
+        TextConvertKeyOpts, TextDecryptOpts, TextEncryptOpts, TextGenerateOpts, TextSignOpts,
+        TextSubCommand, TextVerifyOpts,
     },
+    webhook::{WebhookSignOpts, WebhookSubCommand, WebhookVerifyOpts},
 };
 
 use clap::{Parser, Subcommand};
@@ -40,14 +52,36 @@ pub enum SubCommand {
     Csv(CsvOpts),
     #[command(name = "genpass", about = "Generate password")]
     GenPass(GenPassOpts),
-    #[command(subcommand)]
+    #[command(about = "Hash files like sha256sum, or check a checksum file")]
+    Hash(HashOpts),
+    #[command(
+        subcommand,
+        visible_alias = "encoding",
+        about = "Encode or decode data with base64 and other alphabets"
+    )]
     Base64(Base64SubCommand),
+    #[command(subcommand, about = "Convert files to and from data: URIs")]
+    DataUri(DataUriSubCommand),
     #[command(subcommand)]
     Text(TextSubCommand),
     #[command(subcommand)]
     Http(HttpSubCommand),
     #[command(subcommand)]
     Jwt(JwtSubCommand),
+    #[command(subcommand, about = "TOTP/HOTP one-time passwords")]
+    Otp(OtpSubCommand),
+    #[command(
+        subcommand,
+        about = "Manage the keyring of named keys, used as --key @name"
+    )]
+    Key(KeySubCommand),
+    #[command(
+        subcommand,
+        about = "Split a secret into Shamir shares, or combine them"
+    )]
+    Secret(SecretSubCommand),
+    #[command(subcommand, about = "Sign or verify GitHub, Stripe and Slack webhooks")]
+    Webhook(WebhookSubCommand),
 }
 
 impl CmdExector for CsvOpts {
@@ -64,10 +98,19 @@ pub fn verify_file(s: &str) -> Result<String, &'static str> {
     }
 }
 
-pub fn verify_dir(path: &str) -> Result<String, &'static str> {
+/// A key file, or `@name` for a key in the keyring.
+pub fn verify_key(s: &str) -> Result<String, &'static str> {
+    if s.len() > 1 && s.starts_with('@') {
+        Ok(s.into())
+    } else {
+        verify_file(s)
+    }
+}
+
+pub fn verify_dir(path: &str) -> Result<PathBuf, &'static str> {
     let p = Path::new(path);
     if p.exists() && p.is_dir() {
-        Ok(path.into())
+        Ok(p.to_path_buf())
     } else {
         Err("Directory does not exist")
     }
@@ -106,3 +149,10 @@ impl TryInto<i64> for ReadableDuration {
         }
     }
 }
+
+/// A readable duration such as 30m or 7d, in seconds.
+pub fn parse_duration_secs(s: &str) -> Result<u64, &'static str> {
+    let readable = ReadableDuration::new(s.to_string());
+    let secs: i64 = readable.try_into().map_err(|_| "Invalid time format")?;
+    Ok(secs as u64)
+}
@@ -0,0 +1,221 @@
+use anyhow::Ok;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    process::otp::{
+        decode_secret, process_hotp, process_otp_secret_generate, process_otp_uri,
+        process_otp_verify, process_totp, totp_step, OtpUri, MAX_OTP_WINDOW,
+    },
+    utils::current_timestamp,
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum OtpSubCommand {
+    #[command(about = "Generate a random base32 OTP secret")]
+    Generate(OtpGenerateOpts),
+    #[command(about = "Generate a time-based one-time password (RFC 6238)")]
+    Totp(OtpTotpOpts),
+    #[command(about = "Generate a counter-based one-time password (RFC 4226)")]
+    Hotp(OtpHotpOpts),
+    #[command(about = "Verify a TOTP code, or a HOTP code when --counter is given")]
+    Verify(OtpVerifyOpts),
+    #[command(about = "Print an otpauth:// provisioning URI")]
+    Uri(OtpUriOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpGenerateOpts {
+    /// Secret size in bytes
+    #[arg(short, long, default_value_t = 20, value_parser = clap::value_parser!(u16).range(10..=64))]
+    pub bytes: u16,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpTotpOpts {
+    #[arg(short, long)]
+    pub secret: String,
+    #[arg(short, long, default_value_t = 6, value_parser = parse_digits)]
+    pub digits: u32,
+    #[arg(short, long, default_value_t = 30)]
+    pub period: u64,
+    #[arg(short, long, default_value_t = OtpAlgorithm::Sha1)]
+    pub algorithm: OtpAlgorithm,
+    /// Unix timestamp to generate the code for, defaults to now
+    #[arg(short, long)]
+    pub time: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpHotpOpts {
+    #[arg(short, long)]
+    pub secret: String,
+    #[arg(short, long)]
+    pub counter: u64,
+    #[arg(short, long, default_value_t = 6, value_parser = parse_digits)]
+    pub digits: u32,
+    #[arg(short, long, default_value_t = OtpAlgorithm::Sha1)]
+    pub algorithm: OtpAlgorithm,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpVerifyOpts {
+    #[arg(short, long)]
+    pub secret: String,
+    #[arg(long)]
+    pub code: String,
+    /// Expected HOTP counter; codes up to --window counters ahead are accepted
+    #[arg(short, long)]
+    pub counter: Option<u64>,
+    /// Number of steps (TOTP, both directions) or counters (HOTP, ahead) to accept
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(0..=MAX_OTP_WINDOW))]
+    pub window: u64,
+    #[arg(short, long, default_value_t = 6, value_parser = parse_digits)]
+    pub digits: u32,
+    #[arg(short, long, default_value_t = 30)]
+    pub period: u64,
+    #[arg(short, long, default_value_t = OtpAlgorithm::Sha1)]
+    pub algorithm: OtpAlgorithm,
+    #[arg(short, long)]
+    pub time: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpUriOpts {
+    #[arg(short, long)]
+    pub secret: String,
+    #[arg(long)]
+    pub account: String,
+    #[arg(short, long)]
+    pub issuer: Option<String>,
+    /// Initial counter, produces a HOTP URI instead of TOTP
+    #[arg(short, long)]
+    pub counter: Option<u64>,
+    #[arg(short, long, default_value_t = 6, value_parser = parse_digits)]
+    pub digits: u32,
+    #[arg(short, long, default_value_t = 30)]
+    pub period: u64,
+    #[arg(short, long, default_value_t = OtpAlgorithm::Sha1)]
+    pub algorithm: OtpAlgorithm,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl CmdExector for OtpGenerateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_otp_secret_generate(self.bytes as usize));
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpTotpOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let secret = decode_secret(&self.secret)?;
+        let time = match self.time {
+            Some(time) => time,
+            None => current_timestamp()?,
+        };
+        let code = process_totp(&secret, time, self.period, self.digits, self.algorithm)?;
+        println!("{}", code);
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpHotpOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let secret = decode_secret(&self.secret)?;
+        let code = process_hotp(&secret, self.counter, self.digits, self.algorithm)?;
+        println!("{}", code);
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let secret = decode_secret(&self.secret)?;
+        let counters = match self.counter {
+            // hotp counters only move forward, accepting older ones allows replay
+            Some(counter) => counter..=counter.saturating_add(self.window),
+            None => {
+                let time = match self.time {
+                    Some(time) => time,
+                    None => current_timestamp()?,
+                };
+                let step = totp_step(time, self.period)?;
+                step.saturating_sub(self.window)..=step.saturating_add(self.window)
+            }
+        };
+        let code = self.code.trim();
+        match process_otp_verify(&secret, code, counters, self.digits, self.algorithm)? {
+            Some(counter) if self.counter.is_some() => {
+                println!("✓ Code verified, next counter is {}", counter + 1)
+            }
+            Some(_) => println!("✓ Code verified"),
+            None => println!("⚠ Code not verified"),
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpUriOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let secret = decode_secret(&self.secret)?;
+        let uri = OtpUri {
+            secret: &secret,
+            issuer: self.issuer.as_deref(),
+            account: &self.account,
+            algorithm: self.algorithm,
+            digits: self.digits,
+            counter: self.counter,
+            period: self.period,
+        };
+        println!("{}", process_otp_uri(&uri));
+        Ok(())
+    }
+}
+
+fn parse_digits(s: &str) -> Result<u32, &'static str> {
+    match s.parse::<u32>() {
+        core::result::Result::Ok(digits) if (6..=8).contains(&digits) => {
+            core::result::Result::Ok(digits)
+        }
+        _ => Err("Digits must be between 6 and 8"),
+    }
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid otp algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(algorithm: OtpAlgorithm) -> Self {
+        match algorithm {
+            OtpAlgorithm::Sha1 => "sha1",
+            OtpAlgorithm::Sha256 => "sha256",
+            OtpAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Ok};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use std::io::Write;
+
+use super::{verify_file, verify_key};
+use crate::{
+    process::{
+        keyring::read_key,
+        secret::{process_secret_combine, process_secret_split, Share},
+    },
+    utils::{open_writer, read_content, write_private_file},
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum SecretSubCommand {
+    #[command(about = "Split a secret into shares, any --threshold of which recover it")]
+    Split(SecretSplitOpts),
+    #[command(about = "Recover a secret from its shares")]
+    Combine(SecretCombineOpts),
+}
+
+// rcli secret split -i blake3.key --threshold 3 --shares 5
+#[derive(Debug, Parser)]
+pub struct SecretSplitOpts {
+    /// Secret to split, "-" for stdin or @name for a key in the keyring
+    #[arg(short, long, value_parser = verify_key, default_value = "-")]
+    pub input: String,
+    /// Number of shares needed to recover the secret
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(2..))]
+    pub threshold: u8,
+    /// Number of shares to make
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u8).range(2..))]
+    pub shares: u8,
+}
+
+#[derive(Debug, Parser)]
+pub struct SecretCombineOpts {
+    /// Files with shares, one per line, may be repeated; "-" for stdin
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: Vec<String>,
+    /// File for the recovered secret, "-" for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+}
+
+impl CmdExector for SecretSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let secret = read_key(&self.input, None)?;
+        for share in process_secret_split(&secret, self.threshold, self.shares)? {
+            println!("{}", share);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for SecretCombineOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut shares = Vec::new();
+        for input in &self.input {
+            let text = String::from_utf8(read_content(input)?)?;
+            for (n, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let share: Share = line
+                    .parse()
+                    .map_err(|e| anyhow!("{} line {}: {}", input, n + 1, e))?;
+                shares.push(share);
+            }
+        }
+
+        let secret = process_secret_combine(&shares)?;
+        // the secret is written as it was split, like a key file
+        if self.output == "-" {
+            let mut writer = open_writer(&self.output)?;
+            writer.write_all(&secret)?;
+            writer.flush()?;
+        } else {
+            write_private_file(&self.output, &secret)?;
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,47 @@
 use anyhow::{Ok, Result};
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use base64::{
+    engine::general_purpose::URL_SAFE_NO_PAD, read::DecoderReader, write::EncoderWriter, Engine,
+};
 use enum_dispatch::enum_dispatch;
-use std::{fmt::Display, fs, path::PathBuf, str::FromStr};
+use std::{
+    fmt::Display,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::{
-    process::text::{
-        process_text_encrypt, process_text_key_generate, process_text_sign, process_text_verify,
+    process::{
+        age::{
+            parse_identities, process_age_decrypt, process_age_encrypt,
+            process_age_encrypt_armored, AgeRecipient,
+        },
+        base64::SkipWhitespace,
+        container::{process_container_encrypt, Secret},
+        envelope::{process_envelope_sign, process_envelope_verify, SignatureEnvelope},
+        keyfile::Ed25519Key,
+        keyring::{read_key, read_keyring_key, Keyring},
+        minisign::{
+            default_trusted_comment, process_minisign_sign, process_minisign_verify,
+            MinisignPublicKey, MinisignSecretKey, MinisignSignature,
+        },
+        text::{
+            encode_key, process_text_decrypt, process_text_key_generate, process_text_sign,
+            process_text_sign_file, process_text_verify, process_text_verify_file, HashOptions,
+            KdfParams,
+        },
+    },
+    utils::{
+        current_timestamp, open_reader, open_writer, read_content, trim_newline,
+        write_file_replacing, write_private_file,
     },
-    utils::{open_reader, read_content},
     CmdExector,
 };
 
-use super::{verify_dir, verify_file};
-use clap::{Parser, Subcommand};
+use super::{key::KeyType, parse_duration_secs, verify_dir, verify_file, verify_key};
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Subcommand)]
 #[enum_dispatch(CmdExector)]
@@ -21,87 +50,376 @@ pub enum TextSubCommand {
     Sign(TextSignOpts),
     #[command(about = "Verify a message with a public/session key and signature")]
     Verify(TextVerifyOpts),
-    #[command(about = "Encrypt a message with chacha20poly1305")]
+    #[command(
+        about = "Encrypt a message with chacha20poly1305, a passphrase or to age recipients"
+    )]
     Encrypt(TextEncryptOpts),
     #[command(about = "Decrypt a message with chacha20poly1305")]
     Decrypt(TextDecryptOpts),
     #[command(
         name = "generate",
-        about = "Generate a random blake3 key or ed25519 key pair"
+        about = "Generate a random blake3 or chacha20poly1305 key, or an ed25519 or x25519 key pair"
     )]
     Generate(TextGenerateOpts),
+    #[command(
+        name = "convert-key",
+        about = "Convert an ed25519 key between raw, hex, base64, PEM, OpenSSH and minisign"
+    )]
+    ConvertKey(TextConvertKeyOpts),
 }
 
 #[derive(Debug, Parser)]
 pub struct TextSignOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
-    #[arg(short, long, value_parser = verify_file)]
+    /// Key file, or @name for a key in the keyring
+    #[arg(short, long, value_parser = verify_key)]
     pub key: String,
     #[arg(short, long, default_value_t = TextSignMethod::Blake3, value_parser = parse_text_sign_method)]
     pub method: TextSignMethod,
+    #[command(flatten)]
+    pub hash: HashArgs,
+    /// Signature encoding: base64 (URL-safe) or hex
+    #[arg(short, long, default_value_t = SignatureEncoding::Base64, conflicts_with_all = ["minisign", "envelope"])]
+    pub encoding: SignatureEncoding,
+    /// Print a minisign .minisig signature (ed25519); --key may be a minisign
+    /// secret key
+    #[arg(long, conflicts_with = "envelope")]
+    pub minisign: bool,
+    /// Print a JSON signature envelope that records the method, key ID,
+    /// timestamp, file name and hash
+    #[arg(long)]
+    pub envelope: bool,
+    /// Trusted comment for --minisign, signed too; defaults to the timestamp
+    /// and file name
+    #[arg(long, requires = "minisign")]
+    pub trusted_comment: Option<String>,
+    /// Passphrase of an encrypted minisign secret key or keyring key
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextVerifyOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
-    #[arg(short, long, value_parser = verify_file)]
+    /// Key file, or @name for a key in the keyring
+    #[arg(short, long, value_parser = verify_key)]
     pub key: String,
-    #[arg(short, long)]
-    pub sig: String,
+    #[arg(short, long, required_unless_present_any = ["minisign", "envelope"])]
+    pub sig: Option<String>,
+    /// Verify a minisign .minisig file (ed25519) instead of --sig; --key may
+    /// be a minisign public key
+    #[arg(long, value_parser = verify_file, conflicts_with = "sig")]
+    pub minisign: Option<String>,
+    /// Verify a signature envelope from `text sign --envelope`; the method
+    /// comes from the envelope
+    #[arg(long, value_parser = verify_file, conflicts_with_all = ["sig", "minisign"])]
+    pub envelope: Option<String>,
+    /// Reject envelopes signed longer ago than this, e.g. 30m, 12h or 7d
+    #[arg(long, value_parser = parse_duration_secs, requires = "envelope", conflicts_with_all = ["sig", "minisign"])]
+    pub max_age: Option<u64>,
     #[arg(short, long, default_value_t = TextSignMethod::Blake3, value_parser = parse_text_sign_method)]
     pub method: TextSignMethod,
+    #[command(flatten)]
+    pub hash: HashArgs,
+    /// Encoding of --sig: base64 (URL-safe) or hex
+    #[arg(short, long, default_value_t = SignatureEncoding::Base64, conflicts_with_all = ["minisign", "envelope"])]
+    pub encoding: SignatureEncoding,
+}
+
+#[derive(Debug, Args)]
+pub struct HashArgs {
+    /// Memory-map the input file (blake3)
+    #[arg(long)]
+    pub mmap: bool,
+    /// Memory-map the input file and hash it on all cores (blake3)
+    #[arg(long)]
+    pub parallel: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextGenerateOpts {
-    #[arg(short, long, value_parser = parse_text_sign_method, default_value_t = TextSignMethod::Blake3)]
-    pub method: TextSignMethod,
+    #[arg(short, long, default_value_t = TextKeyMethod::Blake3)]
+    pub method: TextKeyMethod,
     #[arg(short, long, value_parser = verify_dir, default_value = ".")]
     pub output: PathBuf,
+    /// Key file encoding: raw, hex, base64, or pem (PKCS#8 / SPKI), openssh
+    /// and minisign for ed25519; x25519 keys are always written in the age format
+    #[arg(short, long, default_value_t = KeyEncoding::Raw)]
+    pub encoding: KeyEncoding,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Parser)]
+pub struct TextConvertKeyOpts {
+    /// Ed25519 key as raw bytes, hex, base64, PKCS#8 / SPKI PEM or DER, OpenSSH
+    /// or minisign
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// File for the converted key, "-" for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Encoding to write: raw, hex, base64, pem, openssh or minisign
+    #[arg(short, long)]
+    pub to: KeyEncoding,
+    /// The input is a raw, hex or base64 public key; other formats say what they hold
+    #[arg(long)]
+    pub public: bool,
+    /// Write the public half of a private key
+    #[arg(long)]
+    pub extract_public: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TextSignMethod {
     Blake3,
     Ed25519,
+    Ed25519ph,
+    #[serde(rename = "hmac-sha256")]
+    HmacSha256,
+    #[serde(rename = "hmac-sha512")]
+    HmacSha512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    Base64,
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TextKeyMethod {
+    Blake3,
+    Ed25519,
+    ChaCha20Poly1305,
+    X25519,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextCipher {
+    ChaCha20Poly1305,
+    XChaCha20Poly1305,
+    Aes256Gcm,
+    Aes256GcmSiv,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum KeyEncoding {
+    Raw,
+    Hex,
+    Base64,
+    Pem,
+    Openssh,
+    Minisign,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextEncryptOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
-    #[arg(short, long)]
-    pub key: String,
+    /// Hex key, or @name for a key in the keyring
+    #[arg(short, long, required_unless_present_any = ENCRYPT_KEY_SOURCES, conflicts_with_all = ENCRYPT_KEY_SOURCES)]
+    pub key: Option<String>,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    /// AEAD for the payload: xchacha20poly1305, chacha20poly1305, aes-256-gcm
+    /// or aes-256-gcm-siv; recorded in the header, so decrypt needs no flag
+    #[arg(long, default_value_t = TextCipher::XChaCha20Poly1305, conflicts_with = "recipient")]
+    pub cipher: TextCipher,
+    #[command(flatten)]
+    pub kdf: KdfArgs,
+    #[command(flatten)]
+    pub aad: AadArgs,
+    /// Encrypt to an age X25519 recipient (age1...), may be repeated; the
+    /// output is an armored age v1 file
+    #[arg(short, long, conflicts_with_all = AGE_CONFLICTS)]
+    pub recipient: Vec<AgeRecipient>,
+    /// File for the ciphertext, "-" for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Write raw bytes instead of base64 (or age armor), for large payloads
+    #[arg(long)]
+    pub binary: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextDecryptOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
+    /// Hex key, or @name for a key in the keyring
+    #[arg(short, long, required_unless_present_any = DECRYPT_KEY_SOURCES, conflicts_with_all = DECRYPT_KEY_SOURCES)]
+    pub key: Option<String>,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    #[command(flatten)]
+    pub aad: AadArgs,
+    /// Decrypt an age file with the identities in this file (or @name in the
+    /// keyring), may be repeated
+    #[arg(long, value_parser = verify_key, conflicts_with_all = AGE_CONFLICTS)]
+    pub identity: Vec<String>,
+    /// File for the decrypted bytes, "-" for stdout; only replaced once
+    /// decryption succeeds
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Read raw ciphertext from `encrypt --binary` instead of base64; age
+    /// files are detected either way
+    #[arg(long)]
+    pub binary: bool,
+}
+
+// age has no associated data, and no passphrase mode here
+const AGE_CONFLICTS: [&str; 5] = [
+    "passphrase",
+    "passphrase_file",
+    "passphrase_env",
+    "aad",
+    "aad_file",
+];
+const ENCRYPT_KEY_SOURCES: [&str; 4] = [
+    "passphrase",
+    "passphrase_file",
+    "passphrase_env",
+    "recipient",
+];
+const DECRYPT_KEY_SOURCES: [&str; 4] = [
+    "passphrase",
+    "passphrase_file",
+    "passphrase_env",
+    "identity",
+];
+
+/// Where to read the passphrase from.
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+pub struct PassphraseArgs {
+    /// Prompt for the passphrase
     #[arg(short, long)]
-    pub key: String,
+    pub passphrase: bool,
+    /// Read the passphrase from a file, "-" for stdin
+    #[arg(long, value_parser = verify_file)]
+    pub passphrase_file: Option<String>,
+    /// Read the passphrase from an environment variable
+    #[arg(long)]
+    pub passphrase_env: Option<String>,
+}
+
+/// Associated data: authenticated with the ciphertext but not stored in it,
+/// so decryption fails unless the same value is given again.
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+pub struct AadArgs {
+    /// Context to bind the ciphertext to, e.g. a file name or tenant ID
+    #[arg(long)]
+    pub aad: Option<String>,
+    /// Read the associated data from a file
+    #[arg(long, value_parser = verify_file)]
+    pub aad_file: Option<String>,
+}
+
+/// Argon2id cost used when deriving a key from a passphrase.
+#[derive(Debug, Args)]
+pub struct KdfArgs {
+    /// Argon2id memory cost in KiB
+    #[arg(long, default_value_t = KdfParams::default().m_cost)]
+    pub kdf_memory: u32,
+    /// Argon2id number of passes
+    #[arg(long, default_value_t = KdfParams::default().t_cost)]
+    pub kdf_time: u32,
+    /// Argon2id degree of parallelism
+    #[arg(long, default_value_t = KdfParams::default().p_cost)]
+    pub kdf_parallelism: u32,
 }
 
 impl CmdExector for TextSignOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        let passphrase = self.passphrase.read(false)?;
+        let key = read_key(&self.key, passphrase.as_deref())?;
+        if self.minisign {
+            return self.sign_minisign(&key, passphrase.as_deref());
+        }
+        if self.envelope {
+            return self.sign_envelope(&key);
+        }
+        let sig = if self.input == "-" {
+            process_text_sign(open_reader(&self.input)?.as_mut(), &key, self.method)?
+        } else {
+            let path = Path::new(&self.input);
+            process_text_sign_file(path, &key, self.method, self.hash.into())?
+        };
+        println!("{}", self.encoding.encode(&sig));
+        Ok(())
+    }
+}
+
+impl TextSignOpts {
+    fn file_name(&self) -> Option<&str> {
+        Path::new(&self.input)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|_| self.input != "-")
+    }
+
+    fn sign_envelope(&self, key: &[u8]) -> Result<()> {
         let mut msg = open_reader(&self.input)?;
-        let key = read_content(&self.key)?;
-        let sig = process_text_sign(msg.as_mut(), key.as_slice(), self.method)?;
-        let encoded = URL_SAFE_NO_PAD.encode(sig);
-        println!("{}", encoded);
+        let envelope = process_envelope_sign(
+            msg.as_mut(),
+            self.file_name(),
+            key,
+            self.method,
+            current_timestamp()?,
+        )?;
+        println!("{}", serde_json::to_string_pretty(&envelope)?);
+        Ok(())
+    }
+
+    fn sign_minisign(&self, key: &[u8], passphrase: Option<&[u8]>) -> Result<()> {
+        if !matches!(self.method, TextSignMethod::Ed25519) {
+            return Err(anyhow::anyhow!("--minisign needs --method ed25519"));
+        }
+        let text = String::from_utf8_lossy(key);
+        // a minisign secret key keeps its own key ID
+        let key = if MinisignSecretKey::is_secret_key(&text) {
+            MinisignSecretKey::decode(&text, passphrase)?
+        } else {
+            match Ed25519Key::decode(key, false)? {
+                Ed25519Key::Signing(key) => MinisignSecretKey::from_key(key),
+                Ed25519Key::Verifying(_) => {
+                    return Err(anyhow::anyhow!("--minisign needs a private key"))
+                }
+            }
+        };
+        let trusted_comment = match &self.trusted_comment {
+            Some(comment) => comment.clone(),
+            None => default_trusted_comment(self.file_name())?,
+        };
+        let untrusted_comment = "signature from rcli secret key";
+        let mut msg = open_reader(&self.input)?;
+        let sig = process_minisign_sign(msg.as_mut(), &key, &trusted_comment, untrusted_comment)?;
+        print!("{}", sig);
         Ok(())
     }
 }
 
 impl CmdExector for TextVerifyOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut msg = open_reader(&self.input)?;
-        let key = read_content(&self.key)?;
-        let sig = URL_SAFE_NO_PAD.decode(&self.sig)?;
-        let verified =
-            process_text_verify(msg.as_mut(), key.as_slice(), sig.as_slice(), self.method)?;
+        let key = read_key(&self.key, None)?;
+        if let Some(minisig) = &self.minisign {
+            return self.verify_minisign(&key, minisig);
+        }
+        if let Some(envelope) = &self.envelope {
+            return self.verify_envelope(&key, envelope);
+        }
+        let sig = self
+            .encoding
+            .decode(self.sig.as_deref().unwrap_or_default())?;
+        let verified = if self.input == "-" {
+            let mut msg = open_reader(&self.input)?;
+            process_text_verify(msg.as_mut(), &key, &sig, self.method)?
+        } else {
+            let path = Path::new(&self.input);
+            process_text_verify_file(path, &key, &sig, self.method, self.hash.into())?
+        };
         if verified {
             println!("✓ Signature verified");
         } else {
@@ -111,11 +429,78 @@ impl CmdExector for TextVerifyOpts {
     }
 }
 
+impl TextVerifyOpts {
+    fn verify_envelope(&self, key: &[u8], envelope: &str) -> Result<()> {
+        let envelope: SignatureEnvelope = serde_json::from_str(&fs::read_to_string(envelope)?)
+            .map_err(|e| anyhow::anyhow!("invalid signature envelope: {}", e))?;
+        let mut msg = open_reader(&self.input)?;
+        let now = current_timestamp()?;
+        if process_envelope_verify(msg.as_mut(), key, &envelope, self.max_age, now)? {
+            let signed = &envelope.signed;
+            println!("✓ Signature verified");
+            println!("Method: {}", signed.algorithm);
+            println!("Key ID: {}", signed.key_id);
+            println!("Signed: {}s ago", now.saturating_sub(signed.timestamp));
+            if let Some(file) = &signed.file {
+                println!("File: {}", file);
+            }
+        } else {
+            println!("⚠ Signature not verified");
+        }
+        Ok(())
+    }
+
+    fn verify_minisign(&self, key: &[u8], minisig: &str) -> Result<()> {
+        let sig: MinisignSignature = fs::read_to_string(minisig)?.parse()?;
+        let text = String::from_utf8_lossy(key);
+        // other ed25519 keys can only match the ID rcli derives for them
+        let key = match text.parse::<MinisignPublicKey>() {
+            Result::Ok(key) => key,
+            Err(_) => MinisignPublicKey::from_key(Ed25519Key::decode(key, true)?.verifying_key()),
+        };
+        let mut msg = open_reader(&self.input)?;
+        if process_minisign_verify(msg.as_mut(), &key, &sig)? {
+            println!("✓ Signature verified");
+            println!("Trusted comment: {}", sig.trusted_comment);
+        } else {
+            println!("⚠ Signature not verified");
+        }
+        Ok(())
+    }
+}
+
 impl CmdExector for TextGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let key = process_text_key_generate(self.method)?;
         for (filename, contents) in key {
-            fs::write(self.output.join(filename), contents)?;
+            let contents = match self.method {
+                TextKeyMethod::X25519 => contents,
+                TextKeyMethod::Ed25519 => {
+                    let public = filename.ends_with(".pk");
+                    Ed25519Key::decode(&contents, public)?.encode(self.encoding)?
+                }
+                _ => encode_key(&contents, self.encoding)?,
+            };
+            write_private_file(self.output.join(filename), &contents)?;
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for TextConvertKeyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut key = Ed25519Key::decode(&read_content(&self.input)?, self.public)?;
+        if self.extract_public {
+            key = key.public();
+        }
+        let contents = key.encode(self.to)?;
+        // private keys get the same 0600 file as `text generate`
+        if matches!(key, Ed25519Key::Signing(_)) && self.output != "-" {
+            write_private_file(&self.output, &contents)?;
+        } else {
+            let mut writer = open_writer(&self.output)?;
+            writer.write_all(&contents)?;
+            writer.flush()?;
         }
         Ok(())
     }
@@ -124,27 +509,164 @@ impl CmdExector for TextGenerateOpts {
 impl CmdExector for TextEncryptOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let mut msg = open_reader(&self.input)?;
-        let key = hex::decode(&self.key)?;
-        let encrypted = process_text_encrypt(msg.as_mut(), key.as_slice(), true)?;
-        let encoded = URL_SAFE_NO_PAD.encode(encrypted);
-        print!("{}", encoded);
+        let mut writer = open_writer(&self.output)?;
+        if !self.recipient.is_empty() {
+            if self.binary {
+                process_age_encrypt(msg.as_mut(), writer.as_mut(), &self.recipient)?;
+            } else {
+                process_age_encrypt_armored(msg.as_mut(), writer.as_mut(), &self.recipient)?;
+            }
+            writer.flush()?;
+            return Ok(());
+        }
+
+        let passphrase = self.passphrase.read(true)?;
+        let key = hex_key(self.key.as_deref().unwrap_or_default())?;
+        let secret = match &passphrase {
+            Some(passphrase) => Secret::Passphrase(passphrase),
+            None => Secret::Key(&key),
+        };
+        let kdf = self.kdf.into();
+        let aad = self.aad.read()?;
+        if self.binary {
+            process_container_encrypt(
+                msg.as_mut(),
+                writer.as_mut(),
+                secret,
+                self.cipher,
+                &kdf,
+                &aad,
+            )?;
+        } else {
+            let mut encoder = EncoderWriter::new(writer.as_mut(), &URL_SAFE_NO_PAD);
+            process_container_encrypt(msg.as_mut(), &mut encoder, secret, self.cipher, &kdf, &aad)?;
+            encoder.finish()?;
+        }
+        writer.flush()?;
         Ok(())
     }
 }
 
 impl CmdExector for TextDecryptOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = open_reader(&self.input)?;
-        let mut b64msg = Vec::new();
-        reader.read_to_end(&mut b64msg)?;
-        let msg = URL_SAFE_NO_PAD.decode(&b64msg)?;
-        let key = hex::decode(self.key)?;
-        let decoded = process_text_encrypt(&mut msg.as_slice(), key.as_slice(), false)?;
-        println!("{}", String::from_utf8(decoded)?);
+        if self.output != "-" {
+            // never leave a truncated or unauthenticated plaintext behind
+            return write_file_replacing(&self.output, |writer| self.decrypt(writer));
+        }
+        let mut writer = open_writer(&self.output)?;
+        self.decrypt(writer.as_mut())?;
+        writer.flush()?;
         Ok(())
     }
 }
 
+impl TextDecryptOpts {
+    fn decrypt(&self, writer: &mut dyn Write) -> Result<()> {
+        let mut reader = open_reader(&self.input)?;
+        if !self.identity.is_empty() {
+            let mut identities = Vec::new();
+            for file in &self.identity {
+                identities.extend(parse_identities(&String::from_utf8(read_key(
+                    file, None,
+                )?)?)?);
+            }
+            return process_age_decrypt(reader.as_mut(), writer, &identities);
+        }
+
+        let passphrase = self.passphrase.read(false)?;
+        let key = hex_key(self.key.as_deref().unwrap_or_default())?;
+        let secret = match &passphrase {
+            Some(passphrase) => Secret::Passphrase(passphrase),
+            None => Secret::Key(&key),
+        };
+        let mut reader: Box<dyn Read> = if self.binary {
+            reader
+        } else {
+            Box::new(DecoderReader::new(
+                SkipWhitespace::new(reader),
+                &URL_SAFE_NO_PAD,
+            ))
+        };
+        process_text_decrypt(reader.as_mut(), writer, secret, &self.aad.read()?)
+    }
+}
+
+impl PassphraseArgs {
+    /// The passphrase, if one was asked for. Prompts twice when `confirm`.
+    pub(crate) fn read(&self, confirm: bool) -> Result<Option<Vec<u8>>> {
+        let passphrase = if self.passphrase {
+            let passphrase = rpassword::prompt_password("Passphrase: ")?;
+            if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+                return Err(anyhow::anyhow!("passphrases do not match"));
+            }
+            passphrase.into_bytes()
+        } else if let Some(file) = &self.passphrase_file {
+            // a passphrase stored in a file ends with a newline
+            trim_newline(&read_content(file)?).to_vec()
+        } else if let Some(var) = &self.passphrase_env {
+            std::env::var(var)
+                .map_err(|e| anyhow::anyhow!("{}: {}", var, e))?
+                .into_bytes()
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(passphrase))
+    }
+}
+
+// a 32-byte hex key, or @name for a chacha20poly1305 key in the keyring
+fn hex_key(key: &str) -> Result<Vec<u8>> {
+    if let Some(name) = key.strip_prefix('@') {
+        let entry = Keyring::open_default()?.get(name)?;
+        if entry.key_type != KeyType::ChaCha20Poly1305 {
+            return Err(anyhow::anyhow!(
+                "@{} has type {}, encryption needs a {} key",
+                name,
+                entry.key_type,
+                KeyType::ChaCha20Poly1305
+            ));
+        }
+        return read_keyring_key(name, None);
+    }
+    let key = hex::decode(key).map_err(|e| anyhow::anyhow!("invalid hex key: {}", e))?;
+    if key.len() != 32 {
+        return Err(anyhow::anyhow!(
+            "invalid key: hex decodes to {} bytes, require 32",
+            key.len()
+        ));
+    }
+    Ok(key)
+}
+
+impl AadArgs {
+    fn read(&self) -> Result<Vec<u8>> {
+        match (&self.aad, &self.aad_file) {
+            (Some(aad), _) => Ok(aad.as_bytes().to_vec()),
+            (None, Some(file)) => read_content(file),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+}
+
+impl From<KdfArgs> for KdfParams {
+    fn from(args: KdfArgs) -> Self {
+        Self {
+            m_cost: args.kdf_memory,
+            t_cost: args.kdf_time,
+            p_cost: args.kdf_parallelism,
+        }
+    }
+}
+
+impl From<HashArgs> for HashOptions {
+    fn from(args: HashArgs) -> Self {
+        Self {
+            mmap: args.mmap,
+            parallel: args.parallel,
+        }
+    }
+}
+
 fn parse_text_sign_method(s: &str) -> Result<TextSignMethod, anyhow::Error> {
     s.parse()
 }
@@ -156,6 +678,9 @@ impl FromStr for TextSignMethod {
         match s {
             "blake3" => Ok(TextSignMethod::Blake3),
             "ed25519" => Ok(TextSignMethod::Ed25519),
+            "ed25519ph" => Ok(TextSignMethod::Ed25519ph),
+            "hmac-sha256" => Ok(TextSignMethod::HmacSha256),
+            "hmac-sha512" => Ok(TextSignMethod::HmacSha512),
             _ => Err(anyhow::anyhow!("Invalid method")),
         }
     }
@@ -166,6 +691,9 @@ impl From<TextSignMethod> for &str {
         match m {
             TextSignMethod::Blake3 => "blake3",
             TextSignMethod::Ed25519 => "ed25519",
+            TextSignMethod::Ed25519ph => "ed25519ph",
+            TextSignMethod::HmacSha256 => "hmac-sha256",
+            TextSignMethod::HmacSha512 => "hmac-sha512",
         }
     }
 }
@@ -175,3 +703,145 @@ impl Display for TextSignMethod {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl SignatureEncoding {
+    pub fn encode(&self, sig: &[u8]) -> String {
+        match self {
+            SignatureEncoding::Base64 => URL_SAFE_NO_PAD.encode(sig),
+            SignatureEncoding::Hex => hex::encode(sig),
+        }
+    }
+
+    pub fn decode(&self, sig: &str) -> Result<Vec<u8>> {
+        let sig = sig.trim();
+        let decoded = match self {
+            SignatureEncoding::Base64 => URL_SAFE_NO_PAD.decode(sig)?,
+            SignatureEncoding::Hex => hex::decode(sig)?,
+        };
+        Ok(decoded)
+    }
+}
+
+impl FromStr for SignatureEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(SignatureEncoding::Base64),
+            "hex" => Ok(SignatureEncoding::Hex),
+            _ => Err(anyhow::anyhow!("Invalid signature encoding: {}", s)),
+        }
+    }
+}
+
+impl From<SignatureEncoding> for &'static str {
+    fn from(encoding: SignatureEncoding) -> Self {
+        match encoding {
+            SignatureEncoding::Base64 => "base64",
+            SignatureEncoding::Hex => "hex",
+        }
+    }
+}
+
+impl Display for SignatureEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for TextKeyMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(TextKeyMethod::Blake3),
+            "ed25519" => Ok(TextKeyMethod::Ed25519),
+            "chacha20poly1305" => Ok(TextKeyMethod::ChaCha20Poly1305),
+            "x25519" => Ok(TextKeyMethod::X25519),
+            _ => Err(anyhow::anyhow!("Invalid key method: {}", s)),
+        }
+    }
+}
+
+impl From<TextKeyMethod> for &str {
+    fn from(m: TextKeyMethod) -> Self {
+        match m {
+            TextKeyMethod::Blake3 => "blake3",
+            TextKeyMethod::Ed25519 => "ed25519",
+            TextKeyMethod::ChaCha20Poly1305 => "chacha20poly1305",
+            TextKeyMethod::X25519 => "x25519",
+        }
+    }
+}
+
+impl Display for TextKeyMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for TextCipher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chacha20poly1305" => Ok(TextCipher::ChaCha20Poly1305),
+            "xchacha20poly1305" => Ok(TextCipher::XChaCha20Poly1305),
+            "aes-256-gcm" => Ok(TextCipher::Aes256Gcm),
+            "aes-256-gcm-siv" => Ok(TextCipher::Aes256GcmSiv),
+            _ => Err(anyhow::anyhow!("Invalid cipher: {}", s)),
+        }
+    }
+}
+
+impl From<TextCipher> for &str {
+    fn from(c: TextCipher) -> Self {
+        match c {
+            TextCipher::ChaCha20Poly1305 => "chacha20poly1305",
+            TextCipher::XChaCha20Poly1305 => "xchacha20poly1305",
+            TextCipher::Aes256Gcm => "aes-256-gcm",
+            TextCipher::Aes256GcmSiv => "aes-256-gcm-siv",
+        }
+    }
+}
+
+impl Display for TextCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for KeyEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(KeyEncoding::Raw),
+            "hex" => Ok(KeyEncoding::Hex),
+            "base64" => Ok(KeyEncoding::Base64),
+            "pem" => Ok(KeyEncoding::Pem),
+            "openssh" => Ok(KeyEncoding::Openssh),
+            "minisign" => Ok(KeyEncoding::Minisign),
+            _ => Err(anyhow::anyhow!("Invalid key encoding: {}", s)),
+        }
+    }
+}
+
+impl From<KeyEncoding> for &str {
+    fn from(e: KeyEncoding) -> Self {
+        match e {
+            KeyEncoding::Raw => "raw",
+            KeyEncoding::Hex => "hex",
+            KeyEncoding::Base64 => "base64",
+            KeyEncoding::Pem => "pem",
+            KeyEncoding::Openssh => "openssh",
+            KeyEncoding::Minisign => "minisign",
+        }
+    }
+}
+
+impl Display for KeyEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Ok};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use std::{fmt::Display, str::FromStr};
+
+use super::{parse_duration_secs, verify_file};
+use crate::{
+    process::webhook::{process_webhook_sign, process_webhook_verify},
+    utils::{current_timestamp, read_content},
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum WebhookSubCommand {
+    #[command(about = "Sign a request body the way a webhook provider does, for tests")]
+    Sign(WebhookSignOpts),
+    #[command(about = "Verify the signature header of a webhook request")]
+    Verify(WebhookVerifyOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct WebhookSignOpts {
+    #[arg(short, long, default_value_t = WebhookProvider::Github)]
+    pub provider: WebhookProvider,
+    #[arg(short, long)]
+    pub secret: String,
+    /// Request body
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// Unix timestamp to sign with (stripe, slack), defaults to now
+    #[arg(short, long)]
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct WebhookVerifyOpts {
+    #[arg(short, long, default_value_t = WebhookProvider::Github)]
+    pub provider: WebhookProvider,
+    #[arg(short, long)]
+    pub secret: String,
+    /// Value of the signature header: X-Hub-Signature-256, Stripe-Signature
+    /// or X-Slack-Signature
+    #[arg(long)]
+    pub header: String,
+    /// Request body, exactly as received
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// Value of the X-Slack-Request-Timestamp header (slack)
+    #[arg(short, long)]
+    pub timestamp: Option<u64>,
+    /// How far the request timestamp may be from now (stripe, slack)
+    #[arg(long, value_parser = parse_duration_secs, default_value = "5m")]
+    pub tolerance: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookProvider {
+    Github,
+    Stripe,
+    Slack,
+}
+
+impl CmdExector for WebhookSignOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let body = read_content(&self.input)?;
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None => current_timestamp()?,
+        };
+        let headers =
+            process_webhook_sign(self.provider, self.secret.as_bytes(), &body, timestamp)?;
+        for (name, value) in headers {
+            println!("{}: {}", name, value);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for WebhookVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let body = read_content(&self.input)?;
+        let verified = process_webhook_verify(
+            self.provider,
+            self.secret.as_bytes(),
+            &body,
+            &self.header,
+            self.timestamp,
+            self.tolerance,
+            current_timestamp()?,
+        )?;
+        if verified {
+            println!("✓ Signature verified");
+        } else {
+            println!("⚠ Signature not verified");
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for WebhookProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "github" => Ok(WebhookProvider::Github),
+            "stripe" => Ok(WebhookProvider::Stripe),
+            "slack" => Ok(WebhookProvider::Slack),
+            _ => Err(anyhow!("Invalid webhook provider: {}", s)),
+        }
+    }
+}
+
+impl From<WebhookProvider> for &'static str {
+    fn from(provider: WebhookProvider) -> Self {
+        match provider {
+            WebhookProvider::Github => "github",
+            WebhookProvider::Stripe => "stripe",
+            WebhookProvider::Slack => "slack",
+        }
+    }
+}
+
+impl Display for WebhookProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,513 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead, BufReader, Read, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Ok, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    read::DecoderReader,
+    write::EncoderWriter,
+    Engine,
+};
+use bech32::{primitives::decode::CheckedHrpstring, Bech32, Hrp};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use super::{
+    base64::LineWrapper,
+    text::{decrypt_segments, encrypt_segments, AeadCipher},
+};
+use crate::{cli::text::TextCipher, utils::read_full};
+
+pub const AGE_MAGIC: &str = "age-encryption.org/v1";
+pub const AGE_ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const AGE_ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+const X25519_LABEL: &str = "age-encryption.org/v1/X25519";
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+const FILE_KEY_SIZE: usize = 16;
+const PAYLOAD_NONCE_SIZE: usize = 16;
+// stanza bodies and armor are wrapped at 64 columns
+const COLUMNS: usize = 64;
+// a header is a handful of short lines, anything longer is not age
+const MAX_HEADER_LINE: usize = 4096;
+
+/// An age X25519 recipient, `age1...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgeRecipient(PublicKey);
+
+/// An age X25519 identity, `AGE-SECRET-KEY-1...`.
+pub struct AgeIdentity(StaticSecret);
+
+struct Stanza {
+    tag: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl AgeIdentity {
+    pub fn generate() -> Self {
+        Self(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn recipient(&self) -> AgeRecipient {
+        AgeRecipient(PublicKey::from(&self.0))
+    }
+
+    /// The identity file `age-keygen` writes: a comment with the recipient,
+    /// then the secret key.
+    pub fn to_identity_file(&self) -> String {
+        format!("# public key: {}\n{}\n", self.recipient(), self)
+    }
+
+    /// The file key wrapped in an X25519 stanza, or `None` if the stanza was
+    /// made for someone else.
+    fn unwrap_stanza(&self, stanza: &Stanza) -> Result<Option<[u8; FILE_KEY_SIZE]>> {
+        if stanza.args.len() != 1 {
+            return Err(anyhow!("invalid X25519 stanza: expected one argument"));
+        }
+        let share: [u8; 32] = STANDARD_NO_PAD
+            .decode(&stanza.args[0])?
+            .try_into()
+            .map_err(|_| anyhow!("invalid X25519 stanza: bad ephemeral share"))?;
+        if stanza.body.len() != FILE_KEY_SIZE + 16 {
+            return Err(anyhow!("invalid X25519 stanza: bad body length"));
+        }
+        let share = PublicKey::from(share);
+        let shared = self.0.diffie_hellman(&share);
+        if !shared.was_contributory() {
+            return Err(anyhow!("invalid X25519 stanza: low order share"));
+        }
+        let key = wrap_key(&share, &self.recipient().0, shared.as_bytes());
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        Ok(cipher
+            .decrypt(&Nonce::default(), stanza.body.as_slice())
+            .ok()
+            .and_then(|file_key| file_key.try_into().ok()))
+    }
+}
+
+impl AgeRecipient {
+    fn wrap(&self, file_key: &[u8; FILE_KEY_SIZE]) -> Result<Stanza> {
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let share = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&self.0);
+        if !shared.was_contributory() {
+            return Err(anyhow!("invalid recipient: low order point"));
+        }
+        let key = wrap_key(&share, &self.0, shared.as_bytes());
+        let body = ChaCha20Poly1305::new(&key.into())
+            .encrypt(&Nonce::default(), file_key.as_slice())
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(Stanza {
+            tag: "X25519".to_string(),
+            args: vec![STANDARD_NO_PAD.encode(share.as_bytes())],
+            body,
+        })
+    }
+}
+
+impl Stanza {
+    fn write_to(&self, header: &mut String) {
+        header.push_str("-> ");
+        header.push_str(&self.tag);
+        for arg in &self.args {
+            header.push(' ');
+            header.push_str(arg);
+        }
+        header.push('\n');
+        let body = STANDARD_NO_PAD.encode(&self.body);
+        // the body always ends with a short, possibly empty, line
+        for i in (0..=body.len()).step_by(COLUMNS) {
+            header.push_str(&body[i..body.len().min(i + COLUMNS)]);
+            header.push('\n');
+        }
+    }
+}
+
+/// Encrypt to one or more X25519 recipients in the age v1 binary format.
+pub fn process_age_encrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    recipients: &[AgeRecipient],
+) -> Result<()> {
+    if recipients.is_empty() {
+        return Err(anyhow!("at least one recipient is required"));
+    }
+    let mut file_key = [0u8; FILE_KEY_SIZE];
+    OsRng.fill_bytes(&mut file_key);
+
+    let mut header = format!("{}\n", AGE_MAGIC);
+    for recipient in recipients {
+        recipient.wrap(&file_key)?.write_to(&mut header);
+    }
+    header.push_str("---");
+    let mac = header_mac(&file_key, header.as_bytes())
+        .finalize()
+        .into_bytes();
+    header.push(' ');
+    header.push_str(&STANDARD_NO_PAD.encode(mac));
+    header.push('\n');
+    writer.write_all(header.as_bytes())?;
+
+    let mut nonce = [0u8; PAYLOAD_NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    writer.write_all(&nonce)?;
+    // age's chunk nonce is an 11-byte counter and a last flag, which is
+    // STREAM-BE32 with an all-zero prefix
+    let cipher = AeadCipher::try_new(
+        TextCipher::ChaCha20Poly1305,
+        &payload_key(&file_key, &nonce),
+    )?;
+    encrypt_segments(reader, writer, &cipher, &[0; 7], &[])
+}
+
+/// [`process_age_encrypt`] inside the PEM-style armor `age -a` produces.
+pub fn process_age_encrypt_armored(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    recipients: &[AgeRecipient],
+) -> Result<()> {
+    writeln!(writer, "{}", AGE_ARMOR_BEGIN)?;
+    let mut encoder = EncoderWriter::new(LineWrapper::new(&mut *writer, COLUMNS), &STANDARD);
+    process_age_encrypt(reader, &mut encoder, recipients)?;
+    let writer = encoder.finish()?.into_inner();
+    writeln!(writer, "\n{}", AGE_ARMOR_END)?;
+    Ok(())
+}
+
+/// Decrypt an age v1 file, binary or armored, with any of `identities`.
+pub fn process_age_decrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    identities: &[AgeIdentity],
+) -> Result<()> {
+    let mut reader = BufReader::new(reader);
+    if reader.fill_buf()?.starts_with(AGE_ARMOR_BEGIN.as_bytes()) {
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        let mut decoder = DecoderReader::new(ArmorReader::new(reader), &STANDARD);
+        decrypt(&mut BufReader::new(&mut decoder), writer, identities)
+    } else {
+        decrypt(&mut reader, writer, identities)
+    }
+}
+
+fn decrypt(
+    reader: &mut dyn BufRead,
+    writer: &mut dyn Write,
+    identities: &[AgeIdentity],
+) -> Result<()> {
+    let mut header = Vec::new();
+    if read_line(reader, &mut header)? != AGE_MAGIC {
+        return Err(anyhow!("not an age v1 file"));
+    }
+
+    let mut stanzas = Vec::new();
+    let mut line = read_line(reader, &mut header)?;
+    while let Some(rest) = line.strip_prefix("-> ") {
+        let mut args = rest.split(' ').map(str::to_string);
+        let tag = args.next().unwrap_or_default();
+        let mut body = String::new();
+        loop {
+            let chunk = read_line(reader, &mut header)?;
+            body.push_str(&chunk);
+            if chunk.len() < COLUMNS {
+                break;
+            }
+        }
+        stanzas.push(Stanza {
+            tag,
+            args: args.collect(),
+            body: STANDARD_NO_PAD.decode(body)?,
+        });
+        line = read_line(reader, &mut header)?;
+    }
+    let mac = line
+        .strip_prefix("--- ")
+        .ok_or_else(|| anyhow!("invalid age header: expected a stanza or the MAC"))?;
+    let mac = STANDARD_NO_PAD.decode(mac)?;
+    // the MAC covers the header up to and including "---"
+    header.truncate(header.len() - line.len() - 1 + 3);
+
+    let mut file_key = None;
+    for stanza in stanzas.iter().filter(|s| s.tag == "X25519") {
+        for identity in identities {
+            if let Some(key) = identity.unwrap_stanza(stanza)? {
+                file_key = Some(key);
+                break;
+            }
+        }
+    }
+    let file_key = file_key.ok_or_else(|| anyhow!("no identity matched any of the recipients"))?;
+    header_mac(&file_key, &header)
+        .verify_slice(&mac)
+        .map_err(|_| anyhow!("age header MAC mismatch"))?;
+
+    let mut nonce = [0u8; PAYLOAD_NONCE_SIZE];
+    if read_full(reader, &mut nonce)? < nonce.len() {
+        return Err(anyhow!("invalid age payload: missing nonce"));
+    }
+    let cipher = AeadCipher::try_new(
+        TextCipher::ChaCha20Poly1305,
+        &payload_key(&file_key, &nonce),
+    )?;
+    decrypt_segments(reader, writer, &cipher, &[0; 7], &[])
+}
+
+/// Read one `\n` terminated header line, appending its raw bytes to `header`.
+fn read_line(reader: &mut dyn BufRead, header: &mut Vec<u8>) -> Result<String> {
+    let start = header.len();
+    reader
+        .take(MAX_HEADER_LINE as u64)
+        .read_until(b'\n', header)?;
+    if header.last() != Some(&b'\n') {
+        return Err(anyhow!("invalid age header: unterminated line"));
+    }
+    let line = std::str::from_utf8(&header[start..header.len() - 1])?;
+    Ok(line.to_string())
+}
+
+/// Parse an identity file: one `AGE-SECRET-KEY-1...` per line, with `#`
+/// comments and blank lines ignored.
+pub fn parse_identities(contents: &str) -> Result<Vec<AgeIdentity>> {
+    let identities = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(AgeIdentity::from_str)
+        .collect::<Result<Vec<_>>>()?;
+    if identities.is_empty() {
+        return Err(anyhow!("no identities found"));
+    }
+    Ok(identities)
+}
+
+fn wrap_key(share: &PublicKey, recipient: &PublicKey, shared: &[u8]) -> [u8; 32] {
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(share.as_bytes());
+    salt[32..].copy_from_slice(recipient.as_bytes());
+    hkdf_sha256(&salt, shared, X25519_LABEL.as_bytes())
+}
+
+fn payload_key(file_key: &[u8], nonce: &[u8]) -> [u8; 32] {
+    hkdf_sha256(nonce, file_key, b"payload")
+}
+
+fn header_mac(file_key: &[u8], header: &[u8]) -> Hmac<Sha256> {
+    let key = hkdf_sha256(&[], file_key, b"header");
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("hmac takes any key size");
+    mac.update(header);
+    mac
+}
+
+fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut okm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .expect("32 bytes is a valid hkdf output length");
+    okm
+}
+
+fn decode_bech32(s: &str, hrp: &str) -> Result<[u8; 32]> {
+    let checked = CheckedHrpstring::new::<Bech32>(s).map_err(|e| anyhow!(e.to_string()))?;
+    if !checked.hrp().as_str().eq_ignore_ascii_case(hrp) {
+        return Err(anyhow!("expected a \"{}1...\" key, got \"{}\"", hrp, s));
+    }
+    checked
+        .byte_iter()
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow!("invalid key length in \"{}\"", s))
+}
+
+fn encode_bech32(hrp: &str, data: &[u8]) -> String {
+    let hrp = Hrp::parse(hrp).expect("valid hrp");
+    bech32::encode::<Bech32>(hrp, data).expect("key fits in a bech32 string")
+}
+
+impl FromStr for AgeRecipient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(anyhow!("recipients are lowercase: {}", s));
+        }
+        Ok(Self(PublicKey::from(decode_bech32(s, RECIPIENT_HRP)?)))
+    }
+}
+
+impl FromStr for AgeIdentity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().any(|c| c.is_ascii_lowercase()) {
+            return Err(anyhow!("identities are uppercase AGE-SECRET-KEY-1..."));
+        }
+        Ok(Self(StaticSecret::from(decode_bech32(s, IDENTITY_HRP)?)))
+    }
+}
+
+impl Display for AgeRecipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", encode_bech32(RECIPIENT_HRP, self.0.as_bytes()))
+    }
+}
+
+impl Display for AgeIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = encode_bech32(IDENTITY_HRP, self.0.as_bytes());
+        write!(f, "{}", encoded.to_uppercase())
+    }
+}
+
+/// Reader over the base64 lines of an armored file, after the BEGIN line and
+/// up to the END line.
+struct ArmorReader<R> {
+    inner: R,
+    line: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: BufRead> ArmorReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for ArmorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.line.len() {
+            if self.done {
+                return io::Result::Ok(0);
+            }
+            self.line.clear();
+            self.pos = 0;
+            if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "armored age file has no END line",
+                ));
+            }
+            let len = self.line.trim_ascii_end().len();
+            self.line.truncate(len);
+            if self.line == AGE_ARMOR_END.as_bytes() {
+                self.line.clear();
+                self.done = true;
+            }
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        io::Result::Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // produced by the reference implementation (age 0.10) for this identity
+    const IDENTITY: &str = include_str!("../../fixtures/age.key");
+    const AGE_FILE: &[u8] = include_bytes!("../../fixtures/age.txt");
+
+    fn encrypt_decrypt(msg: &[u8], recipients: &[AgeRecipient], identity: &AgeIdentity) {
+        let mut ciphertext = Vec::new();
+        process_age_encrypt(&mut &msg[..], &mut ciphertext, recipients).unwrap();
+        let mut plaintext = Vec::new();
+        process_age_decrypt(
+            &mut &ciphertext[..],
+            &mut plaintext,
+            std::slice::from_ref(identity),
+        )
+        .unwrap();
+        assert_eq!(plaintext, msg);
+    }
+
+    #[test]
+    fn t_age_keys() -> Result<()> {
+        let identity = AgeIdentity::generate();
+        let recipient = identity.recipient();
+        assert!(recipient.to_string().starts_with("age1"));
+        assert!(identity.to_string().starts_with("AGE-SECRET-KEY-1"));
+        assert_eq!(recipient.to_string().parse::<AgeRecipient>()?, recipient);
+
+        let parsed = parse_identities(&identity.to_identity_file())?;
+        assert_eq!(parsed[0].recipient(), recipient);
+
+        let wrong_hrp = identity.to_string().replace("AGE-SECRET-KEY-1", "AGE1");
+        assert!(wrong_hrp.parse::<AgeIdentity>().is_err());
+        assert!(recipient.to_string()[..20].parse::<AgeRecipient>().is_err());
+        assert!(parse_identities("# nothing here\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_age_encrypt_decrypt() -> Result<()> {
+        let alice = AgeIdentity::generate();
+        let bob = AgeIdentity::generate();
+        let recipients = [alice.recipient(), bob.recipient()];
+        let big: Vec<u8> = (0..=255u8).cycle().take(200_000).collect();
+        for msg in [&b""[..], b"hello age", &big] {
+            encrypt_decrypt(msg, &recipients, &alice);
+            encrypt_decrypt(msg, &recipients, &bob);
+        }
+
+        let mut ciphertext = Vec::new();
+        process_age_encrypt(&mut &b"secret"[..], &mut ciphertext, &recipients[..1])?;
+        let mut sink = Vec::new();
+        let carol = AgeIdentity::generate();
+        assert!(process_age_decrypt(&mut &ciphertext[..], &mut sink, &[carol]).is_err());
+
+        // flipping a bit of the header breaks the MAC
+        let mut tampered = ciphertext.clone();
+        let pos = ciphertext.iter().position(|c| *c == b'-').unwrap() + 10;
+        tampered[pos] ^= 1;
+        assert!(process_age_decrypt(&mut &tampered[..], &mut sink, &[alice]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_age_armor() -> Result<()> {
+        let identity = AgeIdentity::generate();
+        let msg = vec![7u8; 1000];
+        let mut armored = Vec::new();
+        process_age_encrypt_armored(&mut &msg[..], &mut armored, &[identity.recipient()])?;
+        let text = String::from_utf8(armored.clone())?;
+        assert!(text.starts_with(AGE_ARMOR_BEGIN));
+        assert!(text.ends_with(&format!("{}\n", AGE_ARMOR_END)));
+        assert!(text.lines().all(|line| line.len() <= COLUMNS));
+
+        let mut plaintext = Vec::new();
+        process_age_decrypt(&mut &armored[..], &mut plaintext, &[identity])?;
+        assert_eq!(plaintext, msg);
+        Ok(())
+    }
+
+    #[test]
+    fn t_age_reference_file() -> Result<()> {
+        let identities = parse_identities(IDENTITY)?;
+        let mut plaintext = Vec::new();
+        process_age_decrypt(&mut &AGE_FILE[..], &mut plaintext, &identities)?;
+        assert_eq!(
+            plaintext,
+            b"encrypted with the reference age implementation\n"
+        );
+        Ok(())
+    }
+}
@@ -1,54 +1,594 @@
-use std::io;
+use std::io::{self, Read, Write};
 
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Ok, Result};
 
-use crate::cli::base64::Base64Method;
-use base64::prelude::*;
+use crate::{cli::base64::EncodingMethod, utils::read_full};
+use base64::{engine::GeneralPurpose, prelude::*, read::DecoderReader, write::EncoderWriter};
+use data_encoding::{Encoding, BASE32, BASE32HEX, BASE32_NOPAD, HEXLOWER, HEXLOWER_PERMISSIVE};
 
-pub fn process_encode(input: &mut dyn io::Read, method: Base64Method) -> Result<String> {
-    let mut buf = String::new();
-    input.read_to_string(&mut buf)?;
-    let s = buf.trim();
+// encode/decode this many blocks at a time for the block based alphabets
+const BLOCKS_PER_CHUNK: usize = 4096;
 
-    let encoded = match method {
-        Base64Method::Standard => BASE64_STANDARD.encode(s),
-        Base64Method::UrlSafe => BASE64_URL_SAFE.encode(s),
-    };
+/// Reader adapter dropping ASCII whitespace, so line-wrapped base64 (PEM,
+/// MIME, `base64 -w 76`) decodes like a single line.
+pub struct SkipWhitespace<R> {
+    inner: R,
+}
+
+impl<R: Read> SkipWhitespace<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return io::Result::Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[len] = buf[i];
+                    len += 1;
+                }
+            }
+            // a chunk of pure whitespace is not EOF, keep reading
+            if len > 0 {
+                return io::Result::Ok(len);
+            }
+        }
+    }
+}
+
+/// Writer adapter breaking the output into lines of `width` characters, as
+/// MIME (76) and PEM (64) expect. A width of 0 disables wrapping.
+pub struct LineWrapper<W> {
+    inner: W,
+    width: usize,
+    column: usize,
+}
+
+impl<W: Write> LineWrapper<W> {
+    pub fn new(inner: W, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            column: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LineWrapper<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.width == 0 {
+            return self.inner.write(buf);
+        }
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.column == self.width {
+                self.inner.write_all(b"\n")?;
+                self.column = 0;
+            }
+            let n = rest.len().min(self.width - self.column);
+            self.inner.write_all(&rest[..n])?;
+            self.column += n;
+            rest = &rest[n..];
+        }
+        io::Result::Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+enum Codec {
+    Base64(&'static GeneralPurpose),
+    // (encoding, input block size in bytes, output block size in symbols)
+    Block(Encoding, usize, usize),
+    Base58,
+    Base85,
+}
+
+fn codec(method: EncodingMethod) -> Codec {
+    match method {
+        EncodingMethod::Standard => Codec::Base64(&BASE64_STANDARD),
+        EncodingMethod::UrlSafe => Codec::Base64(&BASE64_URL_SAFE),
+        EncodingMethod::StandardNoPad => Codec::Base64(&BASE64_STANDARD_NO_PAD),
+        EncodingMethod::UrlSafeNoPad => Codec::Base64(&BASE64_URL_SAFE_NO_PAD),
+        EncodingMethod::Base32 => Codec::Block(BASE32, 5, 8),
+        EncodingMethod::Base32NoPad => Codec::Block(BASE32_NOPAD, 5, 8),
+        EncodingMethod::Base32Hex => Codec::Block(BASE32HEX, 5, 8),
+        EncodingMethod::Base58 => Codec::Base58,
+        EncodingMethod::Base85 => Codec::Base85,
+        EncodingMethod::Hex => Codec::Block(HEXLOWER, 1, 2),
+    }
+}
+
+/// Stream `input` into `output` with the given alphabet, returning the
+/// number of input bytes. Base58 is a big-number encoding and has to buffer
+/// the whole input.
+pub fn process_encode(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    method: EncodingMethod,
+) -> Result<u64> {
+    match codec(method) {
+        Codec::Base64(engine) => {
+            let mut encoder = EncoderWriter::new(output, engine);
+            let n = io::copy(input, &mut encoder)?;
+            encoder.finish()?;
+            Ok(n)
+        }
+        Codec::Block(encoding, block, _) => {
+            encode_chunks(input, output, block, |chunk| encoding.encode(chunk))
+        }
+        Codec::Base58 => {
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf)?;
+            output.write_all(bs58::encode(&buf).into_string().as_bytes())?;
+            Ok(buf.len() as u64)
+        }
+        Codec::Base85 => encode_chunks(input, output, 4, ascii85_encode),
+    }
+}
+
+/// Stream encoded text from `input` into raw bytes in `output`, ignoring
+/// whitespace between characters. Returns the number of decoded bytes.
+pub fn process_decode(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    method: EncodingMethod,
+) -> Result<u64> {
+    let mut input = SkipWhitespace::new(input);
+    match codec(method) {
+        Codec::Base64(engine) => {
+            let mut decoder = DecoderReader::new(input, engine);
+            let n = io::copy(&mut decoder, output)?;
+            Ok(n)
+        }
+        Codec::Block(encoding, _, block) => {
+            // hex accepts either case on input
+            let encoding = if method == EncodingMethod::Hex {
+                HEXLOWER_PERMISSIVE
+            } else {
+                encoding
+            };
+            let mut total = 0;
+            let mut buf = vec![0u8; block * BLOCKS_PER_CHUNK];
+            loop {
+                let n = read_full(&mut input, &mut buf)?;
+                let decoded = encoding
+                    .decode(&buf[..n])
+                    .map_err(|e| anyhow!("invalid {} input: {}", method, e))?;
+                output.write_all(&decoded)?;
+                total += decoded.len() as u64;
+                if n < buf.len() {
+                    return Ok(total);
+                }
+            }
+        }
+        Codec::Base58 => {
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf)?;
+            let decoded = bs58::decode(&buf).into_vec()?;
+            output.write_all(&decoded)?;
+            Ok(decoded.len() as u64)
+        }
+        Codec::Base85 => {
+            // 'z' abbreviations make the block size variable, decode in one go
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf)?;
+            let decoded = ascii85_decode(&buf)?;
+            output.write_all(&decoded)?;
+            Ok(decoded.len() as u64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentType {
+    pub mime: &'static str,
+    pub description: &'static str,
+}
+
+pub struct AutoDecoded {
+    pub method: EncodingMethod,
+    pub content: Vec<ContentType>,
+}
+
+const fn content(mime: &'static str, description: &'static str) -> ContentType {
+    ContentType { mime, description }
+}
+
+// (offset, magic bytes, type), checked in order
+const MAGIC: &[(usize, &[u8], ContentType)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", content("image/png", "PNG image")),
+    (0, b"\xff\xd8\xff", content("image/jpeg", "JPEG image")),
+    (0, b"GIF87a", content("image/gif", "GIF image")),
+    (0, b"GIF89a", content("image/gif", "GIF image")),
+    (8, b"WEBP", content("image/webp", "WebP image")),
+    (0, b"%PDF-", content("application/pdf", "PDF document")),
+    (0, b"\x1f\x8b", content("application/gzip", "gzip data")),
+    (0, b"BZh", content("application/x-bzip2", "bzip2 data")),
+    (0, b"\xfd7zXZ\x00", content("application/x-xz", "xz data")),
+    (
+        0,
+        b"\x28\xb5\x2f\xfd",
+        content("application/zstd", "zstd data"),
+    ),
+    (0, b"PK\x03\x04", content("application/zip", "zip archive")),
+    (257, b"ustar", content("application/x-tar", "tar archive")),
+    (
+        0,
+        b"\x7fELF",
+        content("application/x-executable", "ELF binary"),
+    ),
+    (
+        0,
+        b"\x00asm",
+        content("application/wasm", "WebAssembly module"),
+    ),
+    (0, b"wOF2", content("font/woff2", "WOFF2 font")),
+    (0, b"wOFF", content("font/woff", "WOFF font")),
+    (0, b"OggS", content("audio/ogg", "Ogg media")),
+    (0, b"ID3", content("audio/mpeg", "MP3 audio")),
+];
+
+/// Guess what `data` is from magic bytes, then from its text structure.
+/// Most specific first, empty when nothing matched.
+pub fn sniff_content(data: &[u8]) -> Vec<ContentType> {
+    let mut types: Vec<ContentType> = MAGIC
+        .iter()
+        .filter(|(offset, magic, _)| data.get(*offset..offset + magic.len()) == Some(magic))
+        .map(|(_, _, ty)| *ty)
+        .collect();
+
+    if let core::result::Result::Ok(text) = std::str::from_utf8(data) {
+        let trimmed = text.trim_start();
+        if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+            types.push(content("application/json", "JSON"));
+        } else if trimmed.starts_with("<svg")
+            || trimmed.starts_with("<?xml") && text.contains("<svg")
+        {
+            types.push(content("image/svg+xml", "SVG image"));
+        } else if trimmed.to_ascii_lowercase().starts_with("<!doctype html")
+            || trimmed.starts_with("<html")
+        {
+            types.push(content("text/html", "HTML document"));
+        } else if trimmed.starts_with("<?xml") {
+            types.push(content("application/xml", "XML document"));
+        } else if trimmed.starts_with("-----BEGIN ") {
+            types.push(content("application/x-pem-file", "PEM armored data"));
+        }
+        if !text.contains('\0') {
+            types.push(content("text/plain", "UTF-8 text"));
+        }
+    }
+    types
+}
+
+/// Decode `input` without knowing its alphabet: pick the plausible encodings
+/// from the characters used and the padding, and keep the first one that
+/// decodes. Hex wins over base64 for even-length hex digit strings, and
+/// base32 over base64 for upper-case only strings.
+pub fn process_decode_auto(input: &mut dyn Read, output: &mut dyn Write) -> Result<AutoDecoded> {
+    let mut buf = Vec::new();
+    SkipWhitespace::new(input).read_to_end(&mut buf)?;
+    if buf.is_empty() {
+        return Err(anyhow!("nothing to decode"));
+    }
+
+    for method in detect_encodings(&buf) {
+        let mut decoded = Vec::new();
+        if process_decode(&mut buf.as_slice(), &mut decoded, method).is_ok() {
+            output.write_all(&decoded)?;
+            return Ok(AutoDecoded {
+                method,
+                content: sniff_content(&decoded),
+            });
+        }
+    }
+    Err(anyhow!("could not detect the encoding of the input"))
+}
 
-    Ok(encoded)
+fn detect_encodings(data: &[u8]) -> Vec<EncodingMethod> {
+    let body_len = data.iter().rposition(|c| *c != b'=').map_or(0, |i| i + 1);
+    let body = &data[..body_len];
+    let padded = body_len != data.len();
+    let has = |f: fn(&u8) -> bool| body.iter().any(f);
+    let all = |f: fn(&u8) -> bool| body.iter().all(f);
+
+    let mut candidates = Vec::new();
+    if !padded && body.len().is_multiple_of(2) && all(u8::is_ascii_hexdigit) {
+        candidates.push(EncodingMethod::Hex);
+    }
+    if all(|c| c.is_ascii_uppercase() || (b'2'..=b'7').contains(c)) {
+        candidates.push(if padded {
+            EncodingMethod::Base32
+        } else {
+            EncodingMethod::Base32NoPad
+        });
+    }
+    if all(|c| c.is_ascii_alphanumeric() || b"+/-_".contains(c)) {
+        let url = has(|c| *c == b'-' || *c == b'_');
+        let standard = has(|c| *c == b'+' || *c == b'/');
+        let nopad = !padded && !body.len().is_multiple_of(4);
+        match (url, standard, nopad) {
+            (true, false, false) => candidates.push(EncodingMethod::UrlSafe),
+            (true, false, true) => candidates.push(EncodingMethod::UrlSafeNoPad),
+            (false, _, false) => candidates.push(EncodingMethod::Standard),
+            (false, _, true) => candidates.push(EncodingMethod::StandardNoPad),
+            // mixing both alphabets is not base64
+            (true, true, _) => {}
+        }
+    }
+    if !padded {
+        candidates.push(EncodingMethod::Base58);
+    }
+    candidates.push(EncodingMethod::Base85);
+    candidates
+}
+
+fn encode_chunks(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    block: usize,
+    encode: impl Fn(&[u8]) -> String,
+) -> Result<u64> {
+    let mut total = 0;
+    let mut buf = vec![0u8; block * BLOCKS_PER_CHUNK];
+    loop {
+        let n = read_full(input, &mut buf)?;
+        output.write_all(encode(&buf[..n]).as_bytes())?;
+        total += n as u64;
+        // only the last chunk may end in a partial block
+        if n < buf.len() {
+            return Ok(total);
+        }
+    }
+}
+
+// Adobe Ascii85 without the <~ ~> delimiters, 'z' for an all-zero group
+fn ascii85_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() / 4 * 5 + 5);
+    for chunk in data.chunks(4) {
+        if chunk == [0, 0, 0, 0] {
+            out.push('z');
+            continue;
+        }
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        // a partial group of n bytes keeps n + 1 characters
+        out.extend(digits[..chunk.len() + 1].iter().map(|d| *d as char));
+    }
+    out
 }
 
-pub fn process_decode(input: &mut dyn io::Read, method: Base64Method) -> Result<String> {
-    let mut buf = String::new();
-    input.read_to_string(&mut buf)?;
-    let s = buf.trim();
+fn ascii85_decode(data: &[u8]) -> Result<Vec<u8>> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let data = data.strip_suffix(b"~>").unwrap_or(data);
 
-    let decoded = match method {
-        Base64Method::Standard => BASE64_STANDARD.decode(s)?,
-        Base64Method::UrlSafe => BASE64_URL_SAFE.decode(s)?,
-    };
+    let mut out = Vec::with_capacity(data.len() / 5 * 4 + 4);
+    let mut group = [0u8; 5];
+    let mut len = 0;
+    for (i, c) in data.iter().enumerate() {
+        match c {
+            b'z' if len == 0 => out.extend_from_slice(&[0, 0, 0, 0]),
+            b'!'..=b'u' => {
+                group[len] = c - b'!';
+                len += 1;
+                if len == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    len = 0;
+                }
+            }
+            _ => return Err(anyhow!("invalid base85 character at {}", i)),
+        }
+    }
 
-    let decoded = String::from_utf8(decoded)?;
-    Ok(decoded)
+    match len {
+        0 => {}
+        1 => return Err(anyhow!("invalid base85 length: dangling character")),
+        _ => {
+            // pad with the highest digit, then drop the padding bytes
+            group[len..].fill(84);
+            out.extend_from_slice(&ascii85_group(&group)?[..len - 1]);
+        }
+    }
+    Ok(out)
+}
+
+fn ascii85_group(group: &[u8; 5]) -> Result<[u8; 4]> {
+    let value = group.iter().fold(0u64, |acc, d| acc * 85 + *d as u64);
+    let value = u32::try_from(value).map_err(|_| anyhow!("invalid base85 group"))?;
+    Ok(value.to_be_bytes())
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::utils::open_reader;
+    use crate::utils::read_content;
 
     use super::*;
 
-    fn encode_decode(method: Base64Method) {
-        let infile = "Cargo.toml";
-        let mut reader = open_reader(infile).expect("read content error");
-        let encoded = process_encode(reader.as_mut(), method).expect("encode error");
-        process_decode(&mut (encoded.as_bytes()), method).expect("process encode error");
+    const ALL_METHODS: [EncodingMethod; 10] = [
+        EncodingMethod::Standard,
+        EncodingMethod::UrlSafe,
+        EncodingMethod::StandardNoPad,
+        EncodingMethod::UrlSafeNoPad,
+        EncodingMethod::Base32,
+        EncodingMethod::Base32NoPad,
+        EncodingMethod::Base32Hex,
+        EncodingMethod::Base58,
+        EncodingMethod::Base85,
+        EncodingMethod::Hex,
+    ];
+
+    fn encode(data: &[u8], method: EncodingMethod) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        process_encode(&mut &data[..], &mut encoded, method).expect("encode error");
+        encoded
+    }
+
+    fn decode(data: &[u8], method: EncodingMethod) -> Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        process_decode(&mut &data[..], &mut decoded, method)?;
+        Ok(decoded)
+    }
+
+    fn encode_decode(data: &[u8], method: EncodingMethod) {
+        let encoded = encode(data, method);
+        let decoded = decode(&encoded, method).expect("decode error");
+        assert_eq!(data, decoded.as_slice(), "{} round trip", method);
     }
 
     #[test]
     fn t_encode_decode() {
-        encode_decode(Base64Method::Standard);
-        encode_decode(Base64Method::UrlSafe);
+        let content = read_content("Cargo.toml").expect("read content error");
+        for method in ALL_METHODS {
+            encode_decode(&content, method);
+        }
+    }
+
+    #[test]
+    fn t_encode_decode_binary() {
+        // every byte value, and sizes that are not a multiple of any block
+        let data: Vec<u8> = (0..=255u8).cycle().take(100_003).collect();
+        for method in ALL_METHODS {
+            if method != EncodingMethod::Base58 {
+                encode_decode(&data, method);
+            }
+            encode_decode(&data[..1001], method);
+            encode_decode(b"", method);
+            encode_decode(b"trailing space \n\n", method);
+            encode_decode(&[0, 0, 0, 0, 0, 1], method);
+        }
+    }
+
+    #[test]
+    fn t_known_answers() {
+        let cases: [(EncodingMethod, &[u8], &str); 10] = [
+            (EncodingMethod::Standard, b"\xfb\xff", "+/8="),
+            (EncodingMethod::UrlSafe, b"\xfb\xff", "-_8="),
+            (EncodingMethod::StandardNoPad, b"\xfb\xff", "+/8"),
+            (EncodingMethod::UrlSafeNoPad, b"\xfb\xff", "-_8"),
+            (EncodingMethod::Base32, b"foobar", "MZXW6YTBOI======"),
+            (EncodingMethod::Base32NoPad, b"foobar", "MZXW6YTBOI"),
+            (EncodingMethod::Base32Hex, b"foobar", "CPNMUOJ1E8======"),
+            (EncodingMethod::Base58, b"Hello World!", "2NEpo7TZRRrLZSi2U"),
+            (
+                EncodingMethod::Base85,
+                b"Man is distinguished",
+                "9jqo^BlbD-BleB1DJ+*+F(f,q",
+            ),
+            (EncodingMethod::Hex, b"\xde\xad\xbe\xef", "deadbeef"),
+        ];
+        for (method, data, expected) in cases {
+            assert_eq!(encode(data, method), expected.as_bytes(), "{}", method);
+        }
+        assert_eq!(encode(b"\0\0abc", EncodingMethod::Base58), b"11ZiCa");
+        assert_eq!(encode(b"\0\0\0\0\x01", EncodingMethod::Base85), b"z!<");
+    }
+
+    #[test]
+    fn t_decode_lenient() -> Result<()> {
+        assert_eq!(
+            decode(b"DEADbeef", EncodingMethod::Hex)?,
+            b"\xde\xad\xbe\xef"
+        );
+        assert_eq!(decode(b"<~BOu!rDZ~>", EncodingMethod::Base85)?, b"hello");
+        assert_eq!(
+            decode(b"MZXW 6YTB\nOI", EncodingMethod::Base32NoPad)?,
+            b"foobar"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn t_decode_wrapped() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).collect();
+        for method in ALL_METHODS {
+            let mut wrapped = Vec::new();
+            let mut writer = LineWrapper::new(&mut wrapped, 76);
+            process_encode(&mut data.as_slice(), &mut writer, method)?;
+            assert!(wrapped.split(|c| *c == b'\n').all(|line| line.len() <= 76));
+            assert_eq!(decode(&wrapped, method)?, data);
+        }
+        Ok(())
+    }
+
+    fn auto(data: &[u8]) -> Result<(EncodingMethod, Vec<u8>, Vec<&'static str>)> {
+        let mut decoded = Vec::new();
+        let ret = process_decode_auto(&mut &data[..], &mut decoded)?;
+        let mimes = ret.content.iter().map(|c| c.mime).collect();
+        Ok((ret.method, decoded, mimes))
+    }
+
+    #[test]
+    fn t_decode_auto() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).collect();
+        for method in [
+            EncodingMethod::Standard,
+            EncodingMethod::UrlSafe,
+            EncodingMethod::StandardNoPad,
+            EncodingMethod::UrlSafeNoPad,
+            EncodingMethod::Hex,
+            EncodingMethod::Base32,
+            EncodingMethod::Base32NoPad,
+        ] {
+            let (detected, decoded, _) = auto(&encode(&data, method))?;
+            assert_eq!(detected, method);
+            assert_eq!(decoded, data);
+        }
+
+        let (method, decoded, mimes) = auto(b"eyJhIjogMX0=\n")?;
+        assert_eq!(method, EncodingMethod::Standard);
+        assert_eq!(decoded, br#"{"a": 1}"#);
+        assert_eq!(mimes, ["application/json", "text/plain"]);
+
+        let (method, _, mimes) = auto(b"H4sIAAAAAAAAA8tIzcnJBwCGphA2BQAAAA")?;
+        assert_eq!(method, EncodingMethod::StandardNoPad);
+        assert_eq!(mimes, ["application/gzip"]);
+
+        assert!(auto(b"").is_err());
+        assert!(auto(b"\x00\x01").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_sniff_content() {
+        let mimes =
+            |data: &[u8]| -> Vec<&str> { sniff_content(data).iter().map(|c| c.mime).collect() };
+        assert_eq!(mimes(b"\x89PNG\r\n\x1a\n\0\0"), ["image/png"]);
+        assert_eq!(mimes(b"RIFF\0\0\0\0WEBPVP8 "), ["image/webp"]);
+        assert_eq!(mimes(b"%PDF-1.7"), ["application/pdf", "text/plain"]);
+        assert_eq!(mimes(b"<svg xmlns=\"\"/>"), ["image/svg+xml", "text/plain"]);
+        assert_eq!(mimes(b"hello"), ["text/plain"]);
+        assert!(mimes(b"\xff\xfe\x00").is_empty());
+    }
+
+    #[test]
+    fn t_decode_invalid() {
+        assert!(decode(b"not base64!", EncodingMethod::Standard).is_err());
+        assert!(decode(b"-_-_", EncodingMethod::Standard).is_err());
+        assert!(decode(b"abc", EncodingMethod::Hex).is_err());
+        assert!(decode(b"0OIl", EncodingMethod::Base58).is_err());
+        assert!(decode(b"s8W-!s", EncodingMethod::Base85).is_err());
+        assert!(decode(b"BOu!rD~", EncodingMethod::Base85).is_err());
+        assert!(decode(b"18", EncodingMethod::Base32).is_err());
     }
 }
@@ -0,0 +1,536 @@
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Ok, Result};
+use rand::{rngs::OsRng, RngCore};
+
+use super::text::{
+    decrypt_segments, derive_passphrase_key, encrypt_segments, AeadCipher, KdfParams,
+};
+use crate::{cli::text::TextCipher, utils::read_full};
+
+/// Every container starts with these bytes. Legacy `nonce || ciphertext`
+/// output starts with a random nonce instead.
+pub const CONTAINER_MAGIC: &[u8; 5] = b"RCLI\0";
+pub const CONTAINER_VERSION: u8 = 1;
+
+const FLAG_KDF: u8 = 0x01;
+const FLAG_AAD: u8 = 0x02;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_SIZE: usize = 16;
+const KEY_ID_SIZE: usize = 8;
+const KEY_ID_CONTEXT: &str = "rcli text key id v1";
+const PAYLOAD_SALT_SIZE: usize = 32;
+const PAYLOAD_KEY_CONTEXT: &str = "rcli text payload key v1";
+
+/// The header in front of the payload. All of it is authenticated: its bytes
+/// are part of the associated data of every segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerHeader {
+    pub version: u8,
+    /// AEAD used for the STREAM-BE32 segments.
+    pub cipher: TextCipher,
+    /// Fingerprint of the key, empty when the key comes from a passphrase.
+    pub key_id: Vec<u8>,
+    /// Argon2id cost and salt when the key comes from a passphrase.
+    pub kdf: Option<(KdfParams, [u8; SALT_SIZE])>,
+    /// Whether external associated data was bound; decryption needs the same.
+    pub aad: bool,
+}
+
+/// What the container key is, or is derived from.
+#[derive(Debug, Clone, Copy)]
+pub enum Secret<'a> {
+    Key(&'a [u8]),
+    Passphrase(&'a [u8]),
+}
+
+impl ContainerHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.kdf.is_some() {
+            flags |= FLAG_KDF;
+        }
+        if self.aad {
+            flags |= FLAG_AAD;
+        }
+        let mut header = CONTAINER_MAGIC.to_vec();
+        header.extend_from_slice(&[self.version, cipher_id(self.cipher), flags]);
+        header.push(self.key_id.len() as u8);
+        header.extend_from_slice(&self.key_id);
+        if let Some((kdf, salt)) = &self.kdf {
+            header.push(KDF_ARGON2ID);
+            for cost in [kdf.m_cost, kdf.t_cost, kdf.p_cost] {
+                header.extend_from_slice(&cost.to_be_bytes());
+            }
+            header.push(salt.len() as u8);
+            header.extend_from_slice(salt);
+        }
+        header
+    }
+
+    /// Parse a header, returning it with the raw bytes it was read from.
+    pub fn read_from(reader: &mut dyn Read) -> Result<(Self, Vec<u8>)> {
+        let mut raw = Vec::new();
+        let mut read = |n: usize| -> Result<Vec<u8>> {
+            let mut buf = vec![0u8; n];
+            if read_full(reader, &mut buf)? < n {
+                return Err(anyhow!("invalid container: truncated header"));
+            }
+            raw.extend_from_slice(&buf);
+            Ok(buf)
+        };
+
+        if read(CONTAINER_MAGIC.len())? != CONTAINER_MAGIC {
+            return Err(anyhow!("not an rcli container"));
+        }
+        let fixed = read(4)?;
+        let (version, cipher, flags, key_id_len) = (fixed[0], fixed[1], fixed[2], fixed[3]);
+        if version != CONTAINER_VERSION {
+            return Err(anyhow!("unsupported container version {}", version));
+        }
+        let cipher = cipher_from_id(cipher)?;
+        if flags & !(FLAG_KDF | FLAG_AAD) != 0 {
+            return Err(anyhow!("unsupported container flags {:#04x}", flags));
+        }
+        let key_id = read(key_id_len as usize)?;
+
+        let kdf = if flags & FLAG_KDF != 0 {
+            let params = read(13)?;
+            if params[0] != KDF_ARGON2ID {
+                return Err(anyhow!("unsupported container kdf {}", params[0]));
+            }
+            let cost =
+                |i: usize| u32::from_be_bytes(params[1 + i * 4..5 + i * 4].try_into().unwrap());
+            let kdf = KdfParams {
+                m_cost: cost(0),
+                t_cost: cost(1),
+                p_cost: cost(2),
+            };
+            let salt_len = read(1)?[0] as usize;
+            let salt = read(salt_len)?
+                .try_into()
+                .map_err(|_| anyhow!("invalid container: salt must be {} bytes", SALT_SIZE))?;
+            Some((kdf, salt))
+        } else {
+            None
+        };
+
+        let header = Self {
+            version,
+            cipher,
+            key_id,
+            kdf,
+            aad: flags & FLAG_AAD != 0,
+        };
+        Ok((header, raw))
+    }
+}
+
+fn cipher_id(cipher: TextCipher) -> u8 {
+    match cipher {
+        TextCipher::ChaCha20Poly1305 => 1,
+        TextCipher::XChaCha20Poly1305 => 2,
+        TextCipher::Aes256Gcm => 3,
+        TextCipher::Aes256GcmSiv => 4,
+    }
+}
+
+fn cipher_from_id(id: u8) -> Result<TextCipher> {
+    match id {
+        1 => Ok(TextCipher::ChaCha20Poly1305),
+        2 => Ok(TextCipher::XChaCha20Poly1305),
+        3 => Ok(TextCipher::Aes256Gcm),
+        4 => Ok(TextCipher::Aes256GcmSiv),
+        _ => Err(anyhow!("unsupported cipher suite {}", id)),
+    }
+}
+
+/// Short fingerprint of a symmetric key, stored so decryption can tell a
+/// wrong key from corrupted data.
+pub fn key_id(key: &[u8]) -> [u8; KEY_ID_SIZE] {
+    let hash = blake3::derive_key(KEY_ID_CONTEXT, key);
+    hash[..KEY_ID_SIZE].try_into().unwrap()
+}
+
+/// Key for one container's payload, so a long-term key never meets the AEAD
+/// and nonce prefixes only need to be unique per file.
+pub fn payload_key(key: &[u8], salt: &[u8]) -> [u8; 32] {
+    blake3::derive_key(PAYLOAD_KEY_CONTEXT, &[key, salt].concat())
+}
+
+// the derived payload key is always 32 bytes, so check the key it comes from
+fn check_key(key: &[u8]) -> Result<()> {
+    if key.len() != 32 {
+        return Err(anyhow!("invalid key length: require 32, got {}", key.len()));
+    }
+    Ok(())
+}
+
+/// Encrypt `reader` into a container: header, payload key salt (keys only),
+/// STREAM nonce prefix, segments. `kdf` is only used for passphrases; `aad`
+/// is bound but not stored.
+pub fn process_container_encrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    secret: Secret,
+    cipher: TextCipher,
+    kdf: &KdfParams,
+    aad: &[u8],
+) -> Result<()> {
+    let mut salt = Vec::new();
+    let (key, header) = match secret {
+        Secret::Key(key) => {
+            check_key(key)?;
+            let header = ContainerHeader {
+                version: CONTAINER_VERSION,
+                cipher,
+                key_id: key_id(key).to_vec(),
+                kdf: None,
+                aad: !aad.is_empty(),
+            };
+            salt = vec![0u8; PAYLOAD_SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            (payload_key(key, &salt).to_vec(), header)
+        }
+        Secret::Passphrase(passphrase) => {
+            let mut salt = [0u8; SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            let header = ContainerHeader {
+                version: CONTAINER_VERSION,
+                cipher,
+                key_id: Vec::new(),
+                kdf: Some((*kdf, salt)),
+                aad: !aad.is_empty(),
+            };
+            (
+                derive_passphrase_key(passphrase, &salt, kdf)?.to_vec(),
+                header,
+            )
+        }
+    };
+
+    let aead = AeadCipher::try_new(cipher, &key)?;
+    let header = header.to_bytes();
+    writer.write_all(&header)?;
+    writer.write_all(&salt)?;
+    let mut prefix = vec![0u8; aead.stream_prefix_size()];
+    OsRng.fill_bytes(&mut prefix);
+    writer.write_all(&prefix)?;
+    encrypt_segments(
+        reader,
+        writer,
+        &aead,
+        &prefix,
+        &[header, aad.to_vec()].concat(),
+    )
+}
+
+/// Decrypt a container, checking the secret against what the header asks for.
+pub fn process_container_decrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    secret: Secret,
+    aad: &[u8],
+) -> Result<()> {
+    let (header, raw) = ContainerHeader::read_from(reader)?;
+    if header.aad && aad.is_empty() {
+        return Err(anyhow!(
+            "the data was encrypted with associated data, supply the same --aad"
+        ));
+    }
+    let key = match (secret, &header.kdf) {
+        (Secret::Key(key), None) => {
+            check_key(key)?;
+            let id = key_id(key);
+            if header.key_id != id {
+                return Err(anyhow!(
+                    "wrong key: the data was encrypted with key {}, this is key {}",
+                    hex::encode(&header.key_id),
+                    hex::encode(id)
+                ));
+            }
+            let mut salt = [0u8; PAYLOAD_SALT_SIZE];
+            if read_full(reader, &mut salt)? < salt.len() {
+                return Err(anyhow!("invalid container: missing salt"));
+            }
+            payload_key(key, &salt).to_vec()
+        }
+        (Secret::Passphrase(passphrase), Some((kdf, salt))) => {
+            derive_passphrase_key(passphrase, salt, kdf)?.to_vec()
+        }
+        (Secret::Key(_), Some(_)) => {
+            return Err(anyhow!(
+                "the data was encrypted with a passphrase, not a key"
+            ))
+        }
+        (Secret::Passphrase(_), None) => {
+            return Err(anyhow!(
+                "the data was encrypted with a key, not a passphrase"
+            ))
+        }
+    };
+
+    let aead = AeadCipher::try_new(header.cipher, &key)?;
+    let mut prefix = vec![0u8; aead.stream_prefix_size()];
+    if read_full(reader, &mut prefix)? < prefix.len() {
+        return Err(anyhow!("invalid container: missing nonce"));
+    }
+    decrypt_segments(
+        reader,
+        writer,
+        &aead,
+        &prefix,
+        &[raw, aad.to_vec()].concat(),
+    )
+    .map_err(|e| match (secret, header.aad) {
+        (Secret::Passphrase(_), false) => anyhow!("wrong passphrase or corrupted data: {}", e),
+        (Secret::Passphrase(_), true) => {
+            anyhow!("wrong passphrase, wrong --aad or corrupted data: {}", e)
+        }
+        (Secret::Key(_), true) => anyhow!("wrong --aad or corrupted data: {}", e),
+        (Secret::Key(_), false) => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process::text::STREAM_SEGMENT_SIZE, utils::write_file_replacing};
+
+    const KEY: &[u8] = b"01234567890123456789012345678901";
+    const KDF: KdfParams = KdfParams {
+        m_cost: 1024,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn encrypt(msg: &[u8], secret: Secret, aad: &[u8]) -> Result<Vec<u8>> {
+        let mut ciphertext = Vec::new();
+        process_container_encrypt(
+            &mut &msg[..],
+            &mut ciphertext,
+            secret,
+            TextCipher::XChaCha20Poly1305,
+            &KDF,
+            aad,
+        )?;
+        Ok(ciphertext)
+    }
+
+    fn decrypt(ciphertext: &[u8], secret: Secret, aad: &[u8]) -> Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        process_container_decrypt(&mut &ciphertext[..], &mut plaintext, secret, aad)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn t_container_header() -> Result<()> {
+        let header = ContainerHeader {
+            version: CONTAINER_VERSION,
+            cipher: TextCipher::ChaCha20Poly1305,
+            key_id: key_id(KEY).to_vec(),
+            kdf: Some((KDF, [7u8; SALT_SIZE])),
+            aad: true,
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[..9], b"RCLI\0\x01\x01\x03\x08");
+        let (parsed, raw) = ContainerHeader::read_from(&mut &bytes[..])?;
+        assert_eq!(parsed, header);
+        assert_eq!(raw, bytes);
+
+        for (offset, value) in [(0, b'X'), (5, 2), (6, 9), (7, 0x80)] {
+            let mut bad = bytes.clone();
+            bad[offset] = value;
+            assert!(ContainerHeader::read_from(&mut &bad[..]).is_err());
+        }
+        assert!(ContainerHeader::read_from(&mut &bytes[..12]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_key() -> Result<()> {
+        let msg: Vec<u8> = (0..=255u8).cycle().take(100_000).collect();
+        let ciphertext = encrypt(&msg, Secret::Key(KEY), b"")?;
+        assert!(ciphertext.starts_with(CONTAINER_MAGIC));
+        assert_eq!(decrypt(&ciphertext, Secret::Key(KEY), b"")?, msg);
+
+        let err = decrypt(&ciphertext, Secret::Key(&[1u8; 32]), b"").unwrap_err();
+        assert!(err.to_string().starts_with("wrong key"));
+        assert!(decrypt(&ciphertext, Secret::Passphrase(b"hunter2"), b"").is_err());
+
+        // the header is authenticated, flags cannot be flipped
+        let mut tampered = ciphertext.clone();
+        tampered[7] = FLAG_AAD;
+        assert!(decrypt(&tampered, Secret::Key(KEY), b"x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_payload_key() -> Result<()> {
+        let header_len = 9 + KEY_ID_SIZE;
+        let salt = |ciphertext: &[u8]| ciphertext[header_len..][..PAYLOAD_SALT_SIZE].to_vec();
+        let first = encrypt(b"same message", Secret::Key(KEY), b"")?;
+        let second = encrypt(b"same message", Secret::Key(KEY), b"")?;
+        assert_ne!(salt(&first), salt(&second));
+        let (first_key, second_key) = (
+            payload_key(KEY, &salt(&first)),
+            payload_key(KEY, &salt(&second)),
+        );
+        assert_ne!(first_key, second_key);
+        assert_ne!(first_key.as_slice(), KEY);
+
+        // the salt picks the key, changing it fails authentication
+        let mut tampered = first.clone();
+        tampered[header_len] ^= 1;
+        assert!(decrypt(&tampered, Secret::Key(KEY), b"").is_err());
+        assert!(decrypt(&first[..header_len + 8], Secret::Key(KEY), b"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_key_length() -> Result<()> {
+        let ciphertext = encrypt(b"msg", Secret::Key(KEY), b"")?;
+        for key in [&b""[..], &KEY[..16]] {
+            let err = encrypt(b"msg", Secret::Key(key), b"").unwrap_err();
+            assert!(err.to_string().starts_with("invalid key length"));
+            let err = decrypt(&ciphertext, Secret::Key(key), b"").unwrap_err();
+            assert!(err.to_string().starts_with("invalid key length"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_decrypt_keeps_output() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-container-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let output = dir.join("plain.txt");
+        std::fs::write(&output, b"precious")?;
+
+        let ciphertext = encrypt(b"attack at dawn", Secret::Key(KEY), b"")?;
+        let mut corrupt = ciphertext.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let decrypt_to = |ciphertext: &[u8], secret| {
+            write_file_replacing(&output, |writer| {
+                process_container_decrypt(&mut &ciphertext[..], writer, secret, b"")
+            })
+        };
+        assert!(decrypt_to(&corrupt, Secret::Key(KEY)).is_err());
+        assert!(decrypt_to(&ciphertext, Secret::Key(&[1u8; 32])).is_err());
+        assert_eq!(std::fs::read(&output)?, b"precious");
+        // no temporary file is left behind
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        decrypt_to(&ciphertext, Secret::Key(KEY))?;
+        assert_eq!(std::fs::read(&output)?, b"attack at dawn");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_segments() -> Result<()> {
+        let seg = STREAM_SEGMENT_SIZE;
+        for len in [0, 1, seg - 1, seg, seg + 1, 3 * seg + 5] {
+            let msg: Vec<u8> = (0..=255u8).cycle().take(len).collect();
+            let ciphertext = encrypt(&msg, Secret::Key(KEY), b"")?;
+            let segments = len.div_ceil(seg).max(1);
+            let header_len = 9 + KEY_ID_SIZE + PAYLOAD_SALT_SIZE + 19;
+            assert_eq!(ciphertext.len(), header_len + len + segments * 16);
+            assert_eq!(decrypt(&ciphertext, Secret::Key(KEY), b"")?, msg);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_tamper() -> Result<()> {
+        let seg = STREAM_SEGMENT_SIZE + 16;
+        let msg: Vec<u8> = (0..=255u8)
+            .cycle()
+            .take(3 * STREAM_SEGMENT_SIZE + 5)
+            .collect();
+        let ciphertext = encrypt(&msg, Secret::Key(KEY), b"")?;
+        let (head, body) = ciphertext.split_at(ciphertext.len() - msg.len() - 4 * 16);
+
+        // dropping the last segment leaves a non-final segment at the end
+        let truncated = &ciphertext[..head.len() + 3 * seg];
+        assert!(decrypt(truncated, Secret::Key(KEY), b"").is_err());
+
+        // swapping two segments breaks their counters
+        let mut reordered = head.to_vec();
+        reordered.extend_from_slice(&body[seg..2 * seg]);
+        reordered.extend_from_slice(&body[..seg]);
+        reordered.extend_from_slice(&body[2 * seg..]);
+        assert!(decrypt(&reordered, Secret::Key(KEY), b"").is_err());
+
+        // trailing data after the last segment
+        let mut extended = ciphertext.clone();
+        extended.push(0);
+        assert!(decrypt(&extended, Secret::Key(KEY), b"").is_err());
+
+        let mut flipped = ciphertext.clone();
+        flipped[head.len() + seg + 10] ^= 1;
+        assert!(decrypt(&flipped, Secret::Key(KEY), b"").is_err());
+
+        assert!(decrypt(&ciphertext[..head.len() - 1], Secret::Key(KEY), b"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_ciphers() -> Result<()> {
+        let msg: Vec<u8> = (0..=255u8).cycle().take(70_000).collect();
+        for (cipher, id, prefix) in [
+            (TextCipher::ChaCha20Poly1305, 1, 7),
+            (TextCipher::XChaCha20Poly1305, 2, 19),
+            (TextCipher::Aes256Gcm, 3, 7),
+            (TextCipher::Aes256GcmSiv, 4, 7),
+        ] {
+            let mut ciphertext = Vec::new();
+            let secret = Secret::Key(KEY);
+            process_container_encrypt(&mut &msg[..], &mut ciphertext, secret, cipher, &KDF, b"")?;
+            assert_eq!(ciphertext[6], id);
+            let header_len = 9 + KEY_ID_SIZE + PAYLOAD_SALT_SIZE;
+            // two segments of 64 KiB and the rest, each with a 16-byte tag
+            assert_eq!(ciphertext.len(), header_len + prefix + msg.len() + 2 * 16);
+            assert_eq!(decrypt(&ciphertext, secret, b"")?, msg);
+
+            let mut tampered = ciphertext.clone();
+            *tampered.last_mut().unwrap() ^= 1;
+            assert!(decrypt(&tampered, secret, b"").is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_passphrase() -> Result<()> {
+        let ciphertext = encrypt(b"attack at dawn", Secret::Passphrase(b"hunter2"), b"")?;
+        assert_eq!(
+            decrypt(&ciphertext, Secret::Passphrase(b"hunter2"), b"")?,
+            b"attack at dawn"
+        );
+        assert!(decrypt(&ciphertext, Secret::Passphrase(b"hunter3"), b"").is_err());
+        assert!(decrypt(&ciphertext, Secret::Key(KEY), b"").is_err());
+
+        // lowering the recorded cost changes the key
+        let mut tampered = ciphertext.clone();
+        let (header, _) = ContainerHeader::read_from(&mut &ciphertext[..])?;
+        assert_eq!(header.kdf.unwrap().0, KDF);
+        tampered[9 + 8] = 2;
+        assert!(decrypt(&tampered, Secret::Passphrase(b"hunter2"), b"").is_err());
+        assert!(encrypt(b"msg", Secret::Passphrase(b""), b"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_container_aad() -> Result<()> {
+        let ciphertext = encrypt(b"record", Secret::Key(KEY), b"tenant-1")?;
+        assert_eq!(
+            decrypt(&ciphertext, Secret::Key(KEY), b"tenant-1")?,
+            b"record"
+        );
+        assert!(decrypt(&ciphertext, Secret::Key(KEY), b"tenant-2").is_err());
+        assert!(decrypt(&ciphertext, Secret::Key(KEY), b"").is_err());
+
+        let ciphertext = encrypt(b"record", Secret::Key(KEY), b"")?;
+        assert!(decrypt(&ciphertext, Secret::Key(KEY), b"tenant-1").is_err());
+        Ok(())
+    }
+}
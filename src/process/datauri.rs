@@ -0,0 +1,210 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Ok, Result};
+
+use crate::cli::base64::EncodingMethod;
+
+use super::base64::{process_decode, process_encode, sniff_content};
+
+const DEFAULT_MIME: &str = "application/octet-stream";
+
+// fallback when the content itself is not recognized, or is only "some text"
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("gif", "image/gif"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/x-icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("md", "text/markdown"),
+    ("mjs", "text/javascript"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("ttf", "font/ttf"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("webp", "image/webp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("xml", "application/xml"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DataUri {
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// Pick a MIME type for `data`: magic bytes first, then the file extension,
+/// then the generic text or binary type.
+pub fn detect_mime(data: &[u8], filename: Option<&str>) -> String {
+    let sniffed = sniff_content(data);
+    let by_extension = filename
+        .and_then(|name| Path::new(name).extension()?.to_str())
+        .and_then(|ext| {
+            EXTENSIONS
+                .iter()
+                .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+                .map(|(_, mime)| *mime)
+        });
+
+    let mime = match (sniffed.first(), by_extension) {
+        (Some(content), Some(ext)) if content.mime == "text/plain" => ext,
+        (Some(content), _) => content.mime,
+        (None, Some(ext)) => ext,
+        (None, None) => DEFAULT_MIME,
+    };
+
+    // data: URIs default to US-ASCII, say so when the text is not plain ASCII
+    if mime.starts_with("text/") && !data.is_ascii() {
+        format!("{};charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    }
+}
+
+/// Build a `data:<mime>;base64,...` URI, detecting the MIME type unless given.
+pub fn process_datauri_encode(
+    data: &[u8],
+    filename: Option<&str>,
+    mime: Option<&str>,
+) -> Result<String> {
+    let mime = match mime {
+        Some(mime) => mime.to_string(),
+        None => detect_mime(data, filename),
+    };
+    let mut uri = format!("data:{};base64,", mime).into_bytes();
+    process_encode(&mut &data[..], &mut uri, EncodingMethod::Standard)?;
+    Ok(String::from_utf8(uri)?)
+}
+
+/// Parse an RFC 2397 data URI, base64 or percent-encoded.
+pub fn process_datauri_decode(uri: &str) -> Result<DataUri> {
+    let uri = uri.trim();
+    let rest = uri
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+        .ok_or_else(|| anyhow!("not a data URI: missing \"data:\" prefix"))?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| anyhow!("invalid data URI: missing ','"))?;
+
+    let (mime, base64) = match header.strip_suffix(";base64") {
+        Some(mime) => (mime, true),
+        None => (header, false),
+    };
+    let mime = if mime.is_empty() {
+        "text/plain;charset=US-ASCII".to_string()
+    } else {
+        mime.to_string()
+    };
+
+    let mut payload = percent_decode(payload)?;
+    let data = if base64 {
+        // wrapped payloads are common, the padding is only visible without
+        // the line breaks
+        payload.retain(|b| !b.is_ascii_whitespace());
+        let method = if payload.ends_with(b"=") {
+            EncodingMethod::Standard
+        } else {
+            EncodingMethod::StandardNoPad
+        };
+        let mut data = Vec::new();
+        process_decode(&mut payload.as_slice(), &mut data, method)?;
+        data
+    } else {
+        payload
+    };
+
+    Ok(DataUri { mime, data })
+}
+
+fn percent_decode(s: &str) -> Result<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| anyhow!("invalid percent escape at {}", i))?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn t_detect_mime() {
+        assert_eq!(detect_mime(PNG, Some("logo.jpg")), "image/png");
+        assert_eq!(detect_mime(b"body { }", Some("site.CSS")), "text/css");
+        assert_eq!(detect_mime(b"body { }", None), "text/plain");
+        assert_eq!(
+            detect_mime("héllo".as_bytes(), None),
+            "text/plain;charset=utf-8"
+        );
+        assert_eq!(detect_mime(b"{}", Some("a.txt")), "application/json");
+        assert_eq!(
+            detect_mime(b"\xff\xfe\x00", Some("font.woff2")),
+            "font/woff2"
+        );
+        assert_eq!(detect_mime(b"\xff\xfe\x00", Some("blob")), DEFAULT_MIME);
+    }
+
+    #[test]
+    fn t_datauri_encode_decode() -> Result<()> {
+        let uri = process_datauri_encode(PNG, Some("logo.png"), None)?;
+        assert_eq!(uri, "data:image/png;base64,iVBORw0KGgoAAAANSUhEUg==");
+        let parsed = process_datauri_decode(&uri)?;
+        assert_eq!(parsed.mime, "image/png");
+        assert_eq!(parsed.data, PNG);
+
+        let uri = process_datauri_encode(b"hi", None, Some("text/x-custom"))?;
+        assert_eq!(uri, "data:text/x-custom;base64,aGk=");
+        Ok(())
+    }
+
+    #[test]
+    fn t_datauri_decode_variants() -> Result<()> {
+        let parsed = process_datauri_decode("data:,A%20brief%20note")?;
+        assert_eq!(parsed.mime, "text/plain;charset=US-ASCII");
+        assert_eq!(parsed.data, b"A brief note");
+
+        let parsed = process_datauri_decode("DATA:text/plain;charset=utf-8;base64,aGk\n")?;
+        assert_eq!(parsed.mime, "text/plain;charset=utf-8");
+        assert_eq!(parsed.data, b"hi");
+
+        // wrapped and padded
+        let parsed = process_datauri_decode("data:text/plain;base64,aGVs\nbG8=")?;
+        assert_eq!(parsed.data, b"hello");
+        let parsed = process_datauri_decode("data:;base64,aGVs%0D%0AbG8gd29y\nbGQ=\n")?;
+        assert_eq!(parsed.data, b"hello world");
+
+        assert!(process_datauri_decode("http://example.com").is_err());
+        assert!(process_datauri_decode("data:text/plain").is_err());
+        assert!(process_datauri_decode("data:,%zz").is_err());
+        assert!(process_datauri_decode("data:;base64,!!!!").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Ok, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    container::key_id,
+    text::{
+        hmac_key, load_key, text_signer, text_verifier, Ed25519Signer, Ed25519Verifier, HashOptions,
+    },
+};
+use crate::cli::text::TextSignMethod;
+
+pub const ENVELOPE_VERSION: u8 = 1;
+const PAYLOAD_CONTEXT: &[u8] = b"rcli signature envelope v1\0";
+const HASH_PREFIX: &str = "sha256:";
+// clocks drift, a signature from slightly in the future is still accepted
+const CLOCK_SKEW: u64 = 5 * 60;
+
+/// Everything the signature covers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAttributes {
+    pub version: u8,
+    pub algorithm: TextSignMethod,
+    /// Fingerprint of the public key, or of the blake3 or hmac secret.
+    pub key_id: String,
+    /// Unix time of signing.
+    pub timestamp: u64,
+    /// Name of the signed file, without directories; none for stdin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// "sha256:" and the hex digest of the signed bytes.
+    pub hash: String,
+}
+
+/// A detached signature that says how to check it, stored as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureEnvelope {
+    #[serde(flatten)]
+    pub signed: SignedAttributes,
+    /// URL-safe base64, over the serialized attributes.
+    pub signature: String,
+}
+
+impl SignedAttributes {
+    // struct fields serialize in declaration order, so both sides agree
+    fn payload(&self) -> Result<Vec<u8>> {
+        Ok([PAYLOAD_CONTEXT, &serde_json::to_vec(self)?].concat())
+    }
+}
+
+/// Key fingerprint for `method`, computed from the private key when signing
+/// and from the public key when verifying; both give the same ID.
+pub fn envelope_key_id(key: &[u8], method: TextSignMethod, signing: bool) -> Result<String> {
+    let id = match (method, signing) {
+        (TextSignMethod::Blake3, _) => key_id(&load_key::<32>(key, "blake3")?),
+        (TextSignMethod::HmacSha256 | TextSignMethod::HmacSha512, _) => key_id(hmac_key(key)?),
+        (_, true) => key_id(Ed25519Signer::try_new(key)?.verifying_key().as_bytes()),
+        (_, false) => key_id(Ed25519Verifier::try_new(key)?.verifying_key().as_bytes()),
+    };
+    Ok(hex::encode(id))
+}
+
+/// Hash `reader` and sign the hash together with the metadata.
+pub fn process_envelope_sign(
+    reader: &mut dyn Read,
+    file: Option<&str>,
+    key: &[u8],
+    method: TextSignMethod,
+    timestamp: u64,
+) -> Result<SignatureEnvelope> {
+    let signed = SignedAttributes {
+        version: ENVELOPE_VERSION,
+        algorithm: method,
+        key_id: envelope_key_id(key, method, true)?,
+        timestamp,
+        file: file.map(str::to_string),
+        hash: sha256_hex(reader)?,
+    };
+    let signer = text_signer(key, method, HashOptions::default())?;
+    let signature = signer.sign(&mut signed.payload()?.as_slice())?;
+    Ok(SignatureEnvelope {
+        signed,
+        signature: URL_SAFE_NO_PAD.encode(signature),
+    })
+}
+
+/// Verify `reader` against an envelope with the verifier its algorithm names.
+/// Returns false when the data or the signature does not match; a different
+/// key, an unknown version or a signature older than `max_age` seconds
+/// (when given) are errors.
+pub fn process_envelope_verify(
+    reader: &mut dyn Read,
+    key: &[u8],
+    envelope: &SignatureEnvelope,
+    max_age: Option<u64>,
+    now: u64,
+) -> Result<bool> {
+    let signed = &envelope.signed;
+    if signed.version != ENVELOPE_VERSION {
+        return Err(anyhow!(
+            "unsupported signature envelope version {}",
+            signed.version
+        ));
+    }
+    let key_id = envelope_key_id(key, signed.algorithm, false)?;
+    if key_id != signed.key_id {
+        return Err(anyhow!(
+            "the signature was made with key {}, this is key {}",
+            signed.key_id,
+            key_id
+        ));
+    }
+    if signed.timestamp > now + CLOCK_SKEW {
+        return Err(anyhow!("the signature timestamp is in the future"));
+    }
+    if let Some(max_age) = max_age {
+        let age = now.saturating_sub(signed.timestamp);
+        if age > max_age {
+            return Err(anyhow!(
+                "the signature is {}s old, older than the maximum of {}s",
+                age,
+                max_age
+            ));
+        }
+    }
+
+    let signature = URL_SAFE_NO_PAD
+        .decode(&envelope.signature)
+        .map_err(|e| anyhow!("invalid signature envelope: {}", e))?;
+    let verifier = text_verifier(key, signed.algorithm, HashOptions::default())?;
+    if !verifier.verify(&mut signed.payload()?.as_slice(), &signature)? {
+        return Ok(false);
+    }
+    Ok(sha256_hex(reader)? == signed.hash)
+}
+
+fn sha256_hex(reader: &mut dyn Read) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(format!("{}{}", HASH_PREFIX, hex::encode(hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::text::TextKeyMethod;
+    use crate::process::text::process_text_key_generate;
+
+    const NOW: u64 = 1_700_000_000;
+    const MSG: &[u8] = b"release notes";
+
+    #[test]
+    fn t_envelope_sign_verify() -> Result<()> {
+        let keys = process_text_key_generate(TextKeyMethod::Ed25519)?;
+        let blake3 = process_text_key_generate(TextKeyMethod::Blake3)?;
+        for (method, sk, pk) in [
+            (
+                TextSignMethod::Ed25519,
+                &keys["ed25519.sk"],
+                &keys["ed25519.pk"],
+            ),
+            (
+                TextSignMethod::Ed25519ph,
+                &keys["ed25519.sk"],
+                &keys["ed25519.pk"],
+            ),
+            (
+                TextSignMethod::Blake3,
+                &blake3["blake3.key"],
+                &blake3["blake3.key"],
+            ),
+        ] {
+            let envelope =
+                process_envelope_sign(&mut &MSG[..], Some("notes.txt"), sk, method, NOW)?;
+            let json = serde_json::to_string(&envelope)?;
+            let envelope: SignatureEnvelope = serde_json::from_str(&json)?;
+            assert_eq!(envelope.signed.algorithm, method);
+            assert_eq!(envelope.signed.file.as_deref(), Some("notes.txt"));
+            assert!(process_envelope_verify(
+                &mut &MSG[..],
+                pk,
+                &envelope,
+                None,
+                NOW
+            )?);
+            assert!(!process_envelope_verify(
+                &mut &b"other"[..],
+                pk,
+                &envelope,
+                None,
+                NOW
+            )?);
+
+            // every attribute is signed
+            let mut tampered = envelope.clone();
+            tampered.signed.file = Some("other.txt".to_string());
+            assert!(!process_envelope_verify(
+                &mut &MSG[..],
+                pk,
+                &tampered,
+                None,
+                NOW
+            )?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_envelope_errors() -> Result<()> {
+        let keys = process_text_key_generate(TextKeyMethod::Ed25519)?;
+        let (sk, pk) = (&keys["ed25519.sk"], &keys["ed25519.pk"]);
+        let envelope =
+            process_envelope_sign(&mut &MSG[..], None, sk, TextSignMethod::Ed25519, NOW)?;
+        let verify = |envelope: &SignatureEnvelope, key: &[u8], max_age, now| {
+            process_envelope_verify(&mut &MSG[..], key, envelope, max_age, now)
+        };
+
+        assert!(verify(&envelope, pk, Some(60), NOW + 60)?);
+        let err = verify(&envelope, pk, Some(60), NOW + 61).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the signature is 61s old, older than the maximum of 60s"
+        );
+        assert!(verify(&envelope, pk, None, NOW - CLOCK_SKEW - 1).is_err());
+
+        let other = process_text_key_generate(TextKeyMethod::Ed25519)?;
+        let err = verify(&envelope, &other["ed25519.pk"], None, NOW).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("the signature was made with key"));
+
+        let mut tampered = envelope.clone();
+        tampered.signed.version = 2;
+        assert!(verify(&tampered, pk, None, NOW).is_err());
+        // the timestamp is signed, it cannot be moved forward to pass --max-age
+        let mut tampered = envelope.clone();
+        tampered.signed.timestamp = NOW + 100;
+        assert!(!verify(&tampered, pk, Some(60), NOW + 100)?);
+        Ok(())
+    }
+}
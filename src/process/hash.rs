@@ -0,0 +1,382 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Ok, Result};
+use sha2::{Digest, Sha256, Sha512};
+use sha3::{Sha3_256, Sha3_512};
+
+use crate::{cli::hash::HashAlgorithm, utils::open_reader};
+
+/// Hash everything `reader` returns.
+pub fn process_hash(reader: &mut dyn Read, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    match algorithm {
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(reader, &mut hasher)?;
+            Ok(hasher.finalize().as_bytes().to_vec())
+        }
+        HashAlgorithm::Sha256 => digest::<Sha256>(reader),
+        HashAlgorithm::Sha512 => digest::<Sha512>(reader),
+        HashAlgorithm::Sha3_256 => digest::<Sha3_256>(reader),
+        HashAlgorithm::Sha3_512 => digest::<Sha3_512>(reader),
+    }
+}
+
+/// Hash a file, or stdin for "-".
+pub fn process_hash_file(path: &str, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let mut reader = open_reader(path).map_err(|e| anyhow!("{}: {}", path, e))?;
+    process_hash(reader.as_mut(), algorithm).map_err(|e| anyhow!("{}: {}", path, e))
+}
+
+fn digest<D: Digest + Write>(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Expand directories into the files below them, sorted by name so the
+/// output is stable. Symlinked directories are skipped, they may loop.
+pub fn collect_inputs(inputs: &[String]) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input != "-" && Path::new(input).is_dir() {
+            walk_dir(Path::new(input), &mut files)?;
+        } else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk_dir(&path, files)?;
+        } else if path.is_file() {
+            let name = path
+                .to_str()
+                .ok_or_else(|| anyhow!("{}: file name is not valid UTF-8", path.display()))?;
+            files.push(name.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// A checksum line, GNU coreutils (`digest  file`) or BSD (`TAG (file) =
+/// digest`) style. Names with a backslash or line break are escaped and the
+/// line starts with a backslash, like coreutils does.
+pub fn format_checksum(digest: &[u8], file: &str, algorithm: HashAlgorithm, bsd: bool) -> String {
+    let (prefix, name) = escape(file);
+    if bsd {
+        format!(
+            "{}{} ({}) = {}",
+            prefix,
+            algorithm.tag(),
+            name,
+            hex::encode(digest)
+        )
+    } else {
+        format!("{}{}  {}", prefix, hex::encode(digest), name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumLine {
+    pub algorithm: HashAlgorithm,
+    pub digest: Vec<u8>,
+    pub file: String,
+}
+
+/// Parse a line of a checksum file; GNU lines don't name the algorithm, they
+/// use `algorithm`. Returns `None` for a malformed line.
+pub fn parse_checksum_line(line: &str, algorithm: HashAlgorithm) -> Option<ChecksumLine> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let bsd = line
+        .split_once(" (")
+        .and_then(|(tag, rest)| Some((HashAlgorithm::from_tag(tag)?, rest)));
+    let (algorithm, name, digest) = match bsd {
+        Some((algorithm, rest)) => {
+            let (name, digest) = rest.rsplit_once(") = ")?;
+            (algorithm, name, digest)
+        }
+        None => {
+            let (digest, rest) = line.split_once(' ')?;
+            // the second separator is '*' for binary mode, which changes nothing here
+            let name = rest.strip_prefix([' ', '*'])?;
+            (algorithm, name, digest)
+        }
+    };
+
+    let digest = hex::decode(digest).ok()?;
+    if digest.len() != algorithm.size() || name.is_empty() {
+        return None;
+    }
+    let file = if escaped {
+        unescape(name)?
+    } else {
+        name.to_string()
+    };
+    Some(ChecksumLine {
+        algorithm,
+        digest,
+        file,
+    })
+}
+
+fn escape(file: &str) -> (&'static str, String) {
+    if file.contains(['\\', '\n', '\r']) {
+        let name = file
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        ("\\", name)
+    } else {
+        ("", file.to_string())
+    }
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut file = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            file.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => file.push('\\'),
+            'n' => file.push('\n'),
+            'r' => file.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(file)
+}
+
+/// Outcome of `--check`, counted per line.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CheckSummary {
+    pub ok: usize,
+    pub failed: usize,
+    pub unreadable: usize,
+    pub malformed: usize,
+}
+
+impl CheckSummary {
+    /// Every listed file was read and matched, and at least one was checked.
+    pub fn passed(&self) -> bool {
+        self.failed == 0 && self.unreadable == 0 && self.ok > 0
+    }
+
+    /// The warnings `sha256sum -c` prints after the per-file lines.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.malformed > 0 {
+            warnings.push(plural(
+                self.malformed,
+                "line is improperly formatted",
+                "lines are improperly formatted",
+            ));
+        }
+        if self.unreadable > 0 {
+            warnings.push(plural(
+                self.unreadable,
+                "listed file could not be read",
+                "listed files could not be read",
+            ));
+        }
+        if self.failed > 0 {
+            warnings.push(plural(
+                self.failed,
+                "computed checksum did NOT match",
+                "computed checksums did NOT match",
+            ));
+        }
+        if self.ok == 0 && self.failed == 0 && self.unreadable == 0 {
+            warnings.push("no file was verified".to_string());
+        }
+        warnings
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+/// Check every file listed in `sums`, writing `file: OK` or `file: FAILED`
+/// lines to `writer`. Paths are relative to the working directory; blank
+/// lines and `#` comments are skipped.
+pub fn process_hash_check(
+    sums: &str,
+    algorithm: HashAlgorithm,
+    ignore_missing: bool,
+    quiet: bool,
+    writer: &mut dyn Write,
+) -> Result<CheckSummary> {
+    let mut summary = CheckSummary::default();
+    let mut formatted = false;
+    for line in sums.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(entry) = parse_checksum_line(line, algorithm) else {
+            summary.malformed += 1;
+            continue;
+        };
+        formatted = true;
+
+        let (prefix, name) = escape(&entry.file);
+        let name = format!("{}{}", prefix, name);
+        if ignore_missing && entry.file != "-" && !Path::new(&entry.file).exists() {
+            continue;
+        }
+        match process_hash_file(&entry.file, entry.algorithm) {
+            Result::Ok(digest) if digest == entry.digest => {
+                summary.ok += 1;
+                if !quiet {
+                    writeln!(writer, "{}: OK", name)?;
+                }
+            }
+            Result::Ok(_) => {
+                summary.failed += 1;
+                writeln!(writer, "{}: FAILED", name)?;
+            }
+            Err(_) => {
+                summary.unreadable += 1;
+                writeln!(writer, "{}: FAILED open or read", name)?;
+            }
+        }
+    }
+    if !formatted {
+        return Err(anyhow!("no properly formatted checksum lines found"));
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_hash_known_answers() -> Result<()> {
+        for (algorithm, expected) in [
+            (HashAlgorithm::Blake3, "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+            (HashAlgorithm::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (HashAlgorithm::Sha512, "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+            (HashAlgorithm::Sha3_256, "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"),
+            (HashAlgorithm::Sha3_512, "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"),
+        ] {
+            let digest = process_hash(&mut &b"abc"[..], algorithm)?;
+            assert_eq!(hex::encode(digest), expected, "{}", algorithm);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_checksum_lines() {
+        let digest = [0xab; 32];
+        let gnu = format_checksum(&digest, "a b.txt", HashAlgorithm::Sha256, false);
+        assert_eq!(gnu, format!("{}  a b.txt", "ab".repeat(32)));
+        let bsd = format_checksum(&digest, "a b.txt", HashAlgorithm::Sha3_256, true);
+        assert_eq!(bsd, format!("SHA3-256 (a b.txt) = {}", "ab".repeat(32)));
+
+        let expected = |algorithm, file: &str| ChecksumLine {
+            algorithm,
+            digest: digest.to_vec(),
+            file: file.to_string(),
+        };
+        // BSD lines name their algorithm, -a is ignored
+        assert_eq!(
+            parse_checksum_line(&bsd, HashAlgorithm::Blake3),
+            Some(expected(HashAlgorithm::Sha3_256, "a b.txt"))
+        );
+        assert_eq!(
+            parse_checksum_line(&gnu, HashAlgorithm::Blake3),
+            Some(expected(HashAlgorithm::Blake3, "a b.txt"))
+        );
+        let binary = format!("{} *a b.txt", "ab".repeat(32));
+        assert_eq!(
+            parse_checksum_line(&binary, HashAlgorithm::Sha256),
+            Some(expected(HashAlgorithm::Sha256, "a b.txt"))
+        );
+
+        let odd = "dir\\new\nline";
+        for bsd in [false, true] {
+            let line = format_checksum(&digest, odd, HashAlgorithm::Sha256, bsd);
+            assert!(line.starts_with('\\') && !line.contains('\n'));
+            let entry = parse_checksum_line(&line, HashAlgorithm::Sha256).unwrap();
+            assert_eq!(entry.file, odd);
+        }
+
+        // a sha256 digest is too short for sha512
+        assert_eq!(parse_checksum_line(&gnu, HashAlgorithm::Sha512), None);
+        assert_eq!(
+            parse_checksum_line("not a checksum", HashAlgorithm::Sha256),
+            None
+        );
+        assert_eq!(
+            parse_checksum_line(&format!("{} x", "ab".repeat(32)), HashAlgorithm::Sha256),
+            None
+        );
+    }
+
+    #[test]
+    fn t_hash_check() -> Result<()> {
+        let algorithm = HashAlgorithm::Sha256;
+        let b64 = process_hash_file("fixtures/b64.txt", algorithm)?;
+        let sums = [
+            "# release checksums".to_string(),
+            format_checksum(&b64, "fixtures/b64.txt", algorithm, false),
+            format_checksum(&b64, "fixtures/age.txt", algorithm, true),
+            format_checksum(&b64, "fixtures/missing.txt", algorithm, false),
+            "garbage".to_string(),
+        ]
+        .join("\n");
+
+        let mut output = Vec::new();
+        let summary = process_hash_check(&sums, algorithm, false, false, &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "fixtures/b64.txt: OK\nfixtures/age.txt: FAILED\nfixtures/missing.txt: FAILED open or read\n"
+        );
+        assert_eq!(
+            summary,
+            CheckSummary {
+                ok: 1,
+                failed: 1,
+                unreadable: 1,
+                malformed: 1
+            }
+        );
+        assert!(!summary.passed());
+        assert_eq!(summary.warnings().len(), 3);
+
+        let sums = sums.lines().take(2).collect::<Vec<_>>().join("\n");
+        let summary = process_hash_check(&sums, algorithm, false, true, &mut io::sink())?;
+        assert!(summary.passed());
+        assert!(summary.warnings().is_empty());
+
+        assert!(process_hash_check("garbage", algorithm, false, false, &mut io::sink()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_collect_inputs() -> Result<()> {
+        let files = collect_inputs(&["fixtures".to_string(), "-".to_string()])?;
+        assert!(files.contains(&"fixtures/b64.txt".to_string()));
+        assert_eq!(files.last().unwrap(), "-");
+        let mut sorted = files[..files.len() - 1].to_vec();
+        sorted.sort();
+        assert_eq!(sorted, files[..files.len() - 1]);
+        Ok(())
+    }
+}
@@ -150,9 +150,7 @@ impl PartialOrd for Filename {
     }
 }
 
-impl Eq for Filename {
-    fn assert_receiver_is_total_eq(&self) {}
-}
+impl Eq for Filename {}
 
 impl Ord for Filename {
     fn cmp(&self, other: &Self) -> Ordering {
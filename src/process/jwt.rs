@@ -12,17 +12,17 @@ pub struct Claims {
     pub exp: u64,
 }
 
-pub fn process_jwt_sign(key: &str, claims: &Claims) -> anyhow::Result<String> {
+pub fn process_jwt_sign(key: &[u8], claims: &Claims) -> anyhow::Result<String> {
     let header = Header::new(Algorithm::HS256);
-    let token = encode(&header, claims, &EncodingKey::from_secret(key.as_bytes()))?;
+    let token = encode(&header, claims, &EncodingKey::from_secret(key))?;
     Ok(token)
 }
 
-pub fn process_jwt_verify(token_reader: &mut dyn Read, key: &str) -> anyhow::Result<()> {
+pub fn process_jwt_verify(token_reader: &mut dyn Read, key: &[u8]) -> anyhow::Result<()> {
     let mut token = String::with_capacity(128);
     token_reader.read_to_string(&mut token)?;
 
-    let key = DecodingKey::from_secret(key.as_bytes());
+    let key = DecodingKey::from_secret(key);
     let mut validation = Validation::new(Algorithm::HS256);
     validation.validate_aud = false;
     validation.leeway = 0;
@@ -41,7 +41,7 @@ mod tests {
 
     #[test]
     fn t_jwt_sign_verify() -> Result<()> {
-        let key = "rcli-test-key";
+        let key = b"rcli-test-key";
         let cliams = Claims {
             sub: "homework".to_string(),
             aud: "audience".to_string(),
@@ -53,7 +53,7 @@ mod tests {
 
     #[test]
     fn t_jwt_sign_verify_expired() -> Result<()> {
-        let key = "rcli-test-key";
+        let key = b"rcli-test-key";
         let cliams = Claims {
             sub: "homework".to_string(),
             aud: "audience".to_string(),
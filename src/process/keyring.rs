@@ -0,0 +1,479 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Ok, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    age::parse_identities,
+    container::{key_id, process_container_decrypt, process_container_encrypt, Secret},
+    keyfile::Ed25519Key,
+    text::{hmac_key, load_key, KdfParams},
+};
+use crate::{
+    cli::{
+        key::KeyType,
+        text::{KeyEncoding, TextCipher},
+    },
+    utils::{read_content, write_private_file},
+};
+
+/// Overrides the keyring directory, for scripts and tests.
+pub const KEYRING_ENV: &str = "RCLI_KEYRING";
+/// Unlocks encrypted keys when no other passphrase is given, for scripts.
+pub const PASSPHRASE_ENV: &str = "RCLI_KEYRING_PASSPHRASE";
+const KEY_EXTENSION: &str = "json";
+// binds an encrypted key to its name, so files can't be swapped
+const AAD_PREFIX: &str = "rcli keyring ";
+
+/// A directory of named keys, one JSON file per key.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    dir: PathBuf,
+}
+
+/// A key in the keyring. Everything but the key itself is readable without
+/// the passphrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub key_type: KeyType,
+    pub fingerprint: String,
+    /// Unix time the key was added.
+    pub created: u64,
+    /// Public half of an ed25519 or x25519 key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public: Option<String>,
+    pub encrypted: bool,
+    /// Base64 of the key, or of a passphrase container when encrypted.
+    key: String,
+}
+
+impl Keyring {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$RCLI_KEYRING`, else `$XDG_CONFIG_HOME/rcli/keys`, else
+    /// `~/.config/rcli/keys`.
+    pub fn open_default() -> Result<Self> {
+        if let Some(dir) = env::var_os(KEYRING_ENV) {
+            return Ok(Self::new(dir));
+        }
+        let config = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let home = env::var_os("HOME")
+                    .or_else(|| env::var_os("USERPROFILE"))
+                    .ok_or_else(|| {
+                        anyhow!("cannot find the home directory, set {}", KEYRING_ENV)
+                    })?;
+                Path::new(&home).join(".config")
+            }
+        };
+        Ok(Self::new(config.join("rcli").join("keys")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store `data` under `name`, encrypted when a passphrase is given. The
+    /// key is checked against `key_type` and stored in one canonical form.
+    pub fn add(
+        &self,
+        name: &str,
+        key_type: KeyType,
+        data: &[u8],
+        passphrase: Option<&[u8]>,
+        created: u64,
+    ) -> Result<KeyEntry> {
+        check_name(name)?;
+        let path = self.path(name);
+        if path.exists() {
+            return Err(anyhow!("key {} already exists, remove it first", name));
+        }
+
+        let (material, public, id) = canonical_key(key_type, data)?;
+        let key = match passphrase {
+            Some(passphrase) => {
+                let mut sealed = Vec::new();
+                process_container_encrypt(
+                    &mut material.as_slice(),
+                    &mut sealed,
+                    Secret::Passphrase(passphrase),
+                    TextCipher::XChaCha20Poly1305,
+                    &KdfParams::default(),
+                    aad(name).as_bytes(),
+                )?;
+                sealed
+            }
+            None => material,
+        };
+        let entry = KeyEntry {
+            name: name.to_string(),
+            key_type,
+            fingerprint: hex::encode(key_id(&id)),
+            created,
+            public,
+            encrypted: passphrase.is_some(),
+            key: STANDARD.encode(key),
+        };
+
+        create_private_dir(&self.dir)?;
+        let json = serde_json::to_string_pretty(&entry)?;
+        write_private_file(&path, format!("{}\n", json).as_bytes())?;
+        Ok(entry)
+    }
+
+    pub fn get(&self, name: &str) -> Result<KeyEntry> {
+        check_name(name)?;
+        let path = self.path(name);
+        if !path.exists() {
+            return Err(anyhow!("no key named {} in {}", name, self.dir.display()));
+        }
+        let entry: KeyEntry = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow!("invalid keyring entry {}: {}", path.display(), e))?;
+        if entry.name != name {
+            return Err(anyhow!(
+                "keyring entry {} is named {}",
+                path.display(),
+                entry.name
+            ));
+        }
+        Ok(entry)
+    }
+
+    /// All keys, sorted by name. A missing directory is an empty keyring.
+    pub fn list(&self) -> Result<Vec<KeyEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                entries.push(self.get(name)?);
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<KeyEntry> {
+        let entry = self.get(name)?;
+        fs::remove_file(self.path(name))?;
+        Ok(entry)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, KEY_EXTENSION))
+    }
+}
+
+impl KeyEntry {
+    /// The key as `text` and `jwt` read it from a key file; `passphrase` is
+    /// only needed for an encrypted entry.
+    pub fn material(&self, passphrase: Option<&[u8]>) -> Result<Vec<u8>> {
+        let key = STANDARD
+            .decode(&self.key)
+            .map_err(|e| anyhow!("invalid keyring entry {}: {}", self.name, e))?;
+        if !self.encrypted {
+            return Ok(key);
+        }
+        let passphrase = passphrase
+            .ok_or_else(|| anyhow!("key {} is encrypted, a passphrase is required", self.name))?;
+        let mut material = Vec::new();
+        process_container_decrypt(
+            &mut key.as_slice(),
+            &mut material,
+            Secret::Passphrase(passphrase),
+            aad(&self.name).as_bytes(),
+        )?;
+        Ok(material)
+    }
+}
+
+/// Key material from a file, or from the keyring for `@name`; `passphrase`
+/// is as for [`read_keyring_key`].
+pub fn read_key(key: &str, passphrase: Option<&[u8]>) -> Result<Vec<u8>> {
+    match key.strip_prefix('@') {
+        Some(name) => read_keyring_key(name, passphrase),
+        None => read_content(key),
+    }
+}
+
+/// A key from the default keyring. An encrypted one is unlocked with
+/// `passphrase`, else `$RCLI_KEYRING_PASSPHRASE`, else a prompt.
+pub fn read_keyring_key(name: &str, passphrase: Option<&[u8]>) -> Result<Vec<u8>> {
+    let entry = Keyring::open_default()?.get(name)?;
+    if !entry.encrypted {
+        return entry.material(None);
+    }
+    let passphrase = match (passphrase, env::var(PASSPHRASE_ENV)) {
+        (Some(passphrase), _) => passphrase.to_vec(),
+        (None, Result::Ok(passphrase)) => passphrase.into_bytes(),
+        (None, Err(_)) => {
+            let prompt = format!("Passphrase for @{}: ", name);
+            rpassword::prompt_password(prompt)?.into_bytes()
+        }
+    };
+    entry.material(Some(&passphrase))
+}
+
+/// Canonical key bytes, public half and what the fingerprint covers: the
+/// public key when there is one, the same ID `text sign --envelope` records.
+fn canonical_key(key_type: KeyType, data: &[u8]) -> Result<(Vec<u8>, Option<String>, Vec<u8>)> {
+    let canonical = match key_type {
+        KeyType::Blake3 | KeyType::ChaCha20Poly1305 => {
+            let key = load_key::<32>(data, key_type.into())?;
+            (key.to_vec(), None, key.to_vec())
+        }
+        KeyType::Ed25519 => {
+            // PEM keeps private and public keys apart, both verify; a bare
+            // 32-byte key could be either half
+            let key = Ed25519Key::decode_structured(data)?.ok_or_else(|| {
+                anyhow!(
+                    "a raw, hex or base64 ed25519 key could be private or public, add it as \
+                     PEM or OpenSSH, e.g. from `text convert-key --to pem`"
+                )
+            })?;
+            let public = key.public().encode(KeyEncoding::Openssh)?;
+            let public = String::from_utf8(public)?.trim().to_string();
+            let id = key.verifying_key().to_bytes().to_vec();
+            (key.encode(KeyEncoding::Pem)?, Some(public), id)
+        }
+        KeyType::X25519 => {
+            let text = String::from_utf8(data.to_vec())?;
+            let identities = parse_identities(&text)?;
+            let identity = identities
+                .first()
+                .ok_or_else(|| anyhow!("no x25519 identity in the key"))?;
+            // the first identity alone, without comments
+            let key = format!("{}\n", identity).into_bytes();
+            let public = identity.recipient().to_string();
+            (key, Some(public.clone()), public.into_bytes())
+        }
+        KeyType::Secret => {
+            let key = hmac_key(data)?.to_vec();
+            (key.clone(), None, key)
+        }
+    };
+    Ok(canonical)
+}
+
+fn aad(name: &str) -> String {
+    format!("{}{}", AAD_PREFIX, name)
+}
+
+// names become file names, keep them to a portable set
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(anyhow!(
+            "invalid key name {:?}: use letters, digits, '.', '_' and '-'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn create_private_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    // civil_from_days, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::text::{TextKeyMethod, TextSignMethod};
+    use crate::process::{
+        age::AgeIdentity,
+        envelope::envelope_key_id,
+        text::{process_text_key_generate, process_text_sign, process_text_verify},
+    };
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn keyring(test: &str) -> Result<Keyring> {
+        let dir = env::temp_dir().join(format!("rcli-keyring-{}-{}", test, std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(Keyring::new(dir))
+    }
+
+    #[test]
+    fn t_keyring_add_get_remove() -> Result<()> {
+        let keyring = keyring("add")?;
+        assert!(keyring.list()?.is_empty());
+
+        let blake3 = process_text_key_generate(TextKeyMethod::Blake3)?;
+        let entry = keyring.add("ci", KeyType::Blake3, &blake3["blake3.key"], None, NOW)?;
+        assert_eq!(keyring.get("ci")?, entry);
+        assert_eq!(entry.material(None)?, blake3["blake3.key"]);
+        assert!(keyring
+            .add("ci", KeyType::Blake3, &blake3["blake3.key"], None, NOW)
+            .is_err());
+        // checked against the type
+        assert!(keyring
+            .add("short", KeyType::ChaCha20Poly1305, &[0; 31], None, NOW)
+            .is_err());
+        assert!(keyring
+            .add("../x", KeyType::Secret, b"x", None, NOW)
+            .is_err());
+
+        keyring.add("webhook", KeyType::Secret, b"s3cret\n", None, NOW)?;
+        let names: Vec<_> = keyring
+            .list()?
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["ci", "webhook"]);
+        assert_eq!(keyring.get("webhook")?.material(None)?, b"s3cret");
+
+        keyring.remove("ci")?;
+        assert!(keyring.get("ci").is_err());
+        fs::remove_dir_all(keyring.dir())?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_keyring_ed25519() -> Result<()> {
+        let keyring = keyring("ed25519")?;
+        let keys = process_text_key_generate(TextKeyMethod::Ed25519)?;
+        // bare keys don't say which half they are
+        for raw in ["ed25519.sk", "ed25519.pk"] {
+            assert!(keyring
+                .add("raw", KeyType::Ed25519, &keys[raw], None, NOW)
+                .is_err());
+        }
+        let pem = Ed25519Key::decode(&keys["ed25519.sk"], false)?.encode(KeyEncoding::Pem)?;
+        let entry = keyring.add("release", KeyType::Ed25519, &pem, None, NOW)?;
+        assert!(entry.public.as_deref().unwrap().starts_with("ssh-ed25519 "));
+        // the fingerprint is the key ID signature envelopes record
+        let id = envelope_key_id(&keys["ed25519.pk"], TextSignMethod::Ed25519, false)?;
+        assert_eq!(entry.fingerprint, id);
+
+        // the stored private key signs, and verifies through its public half
+        let key = entry.material(None)?;
+        let sig = process_text_sign(&mut &b"msg"[..], &key, TextSignMethod::Ed25519)?;
+        assert!(process_text_verify(
+            &mut &b"msg"[..],
+            &key,
+            &sig,
+            TextSignMethod::Ed25519
+        )?);
+        let public = entry.public.unwrap();
+        assert!(process_text_verify(
+            &mut &b"msg"[..],
+            public.as_bytes(),
+            &sig,
+            TextSignMethod::Ed25519
+        )?);
+
+        // a structured public key is stored as one, and can't sign
+        let entry = keyring.add("verify", KeyType::Ed25519, public.as_bytes(), None, NOW)?;
+        assert_eq!(entry.public.as_deref(), Some(public.as_str()));
+        let key = entry.material(None)?;
+        assert!(process_text_sign(&mut &b"msg"[..], &key, TextSignMethod::Ed25519).is_err());
+        assert!(process_text_verify(
+            &mut &b"msg"[..],
+            &key,
+            &sig,
+            TextSignMethod::Ed25519
+        )?);
+        fs::remove_dir_all(keyring.dir())?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_keyring_x25519() -> Result<()> {
+        let keyring = keyring("x25519")?;
+        let keys = process_text_key_generate(TextKeyMethod::X25519)?;
+        let identity = parse_identities(std::str::from_utf8(&keys["x25519.key"])?)?.remove(0);
+        let other = AgeIdentity::generate();
+        let file = [keys["x25519.key"].as_slice(), other.to_string().as_bytes()].concat();
+
+        let entry = keyring.add("backup", KeyType::X25519, &file, None, NOW)?;
+        assert_eq!(
+            entry.material(None)?,
+            format!("{}\n", identity).into_bytes()
+        );
+        assert_eq!(entry.public, Some(identity.recipient().to_string()));
+        assert_eq!(
+            entry.public.as_deref().map(str::as_bytes),
+            Some(keys["x25519.pub"].trim_ascii_end())
+        );
+        fs::remove_dir_all(keyring.dir())?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_keyring_encrypted() -> Result<()> {
+        let keyring = keyring("encrypted")?;
+        let key = [7u8; 32];
+        let entry = keyring.add("backup", KeyType::ChaCha20Poly1305, &key, Some(b"pw"), NOW)?;
+        assert!(entry.encrypted);
+        assert_eq!(entry.fingerprint, hex::encode(key_id(&key)));
+        assert!(!fs::read_to_string(keyring.path("backup"))?.contains(&STANDARD.encode(key)));
+
+        assert!(entry.material(None).is_err());
+        assert!(entry.material(Some(b"wrong")).is_err());
+        assert_eq!(entry.material(Some(b"pw"))?, key);
+
+        // the ciphertext is bound to the name
+        let mut renamed = entry.clone();
+        renamed.name = "other".to_string();
+        assert!(renamed.material(Some(b"pw")).is_err());
+        fs::remove_dir_all(keyring.dir())?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(NOW), "2023-11-14 22:13");
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Read},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Ok, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blake2::{digest::consts::U32, Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+
+use super::text::{load_signature, Ed25519Signer, Ed25519Verifier, TextSigner, TextVerifier};
+use crate::utils::current_timestamp;
+
+const SIGALG: &[u8; 2] = b"Ed";
+const SIGALG_PREHASHED: &[u8; 2] = b"ED";
+const KDF_SCRYPT: &[u8; 2] = b"Sc";
+const KDF_NONE: &[u8; 2] = &[0, 0];
+const CHK_BLAKE2B: &[u8; 2] = b"B2";
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+const KEY_ID_SIZE: usize = 8;
+// sig alg, kdf alg, checksum alg, salt, opslimit, memlimit, key id, key, checksum
+const SECRET_KEY_SIZE: usize = 2 + 2 + 2 + 32 + 8 + 8 + KEY_ID_SIZE + 64 + 32;
+// the ceiling minisign itself applies to scrypt parameters
+const SCRYPT_MEMLIMIT_MAX: u64 = 1 << 30;
+const SCRYPT_LOG_N_MAX: u8 = 20;
+
+pub type KeyId = [u8; KEY_ID_SIZE];
+
+/// A minisign public key: the Ed25519 key and the ID signatures refer to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinisignPublicKey {
+    pub key_id: KeyId,
+    pub key: VerifyingKey,
+}
+
+/// A decrypted minisign secret key.
+pub struct MinisignSecretKey {
+    pub key_id: KeyId,
+    pub key: SigningKey,
+}
+
+/// A `.minisig` file. The trusted comment is covered by the global signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinisignSignature {
+    /// Whether the BLAKE2b-512 of the message was signed instead of the message.
+    pub prehashed: bool,
+    pub key_id: KeyId,
+    pub signature: Signature,
+    pub untrusted_comment: String,
+    pub trusted_comment: String,
+    pub global_signature: Signature,
+}
+
+/// minisign key IDs are random; keys made by rcli get one derived from the
+/// public key, so the same key always has the same ID.
+pub fn key_id(key: &VerifyingKey) -> KeyId {
+    let hash = Blake2b512::digest(key.as_bytes());
+    hash[..KEY_ID_SIZE].try_into().unwrap()
+}
+
+/// How minisign prints key IDs: the little-endian u64 in upper-case hex.
+pub fn key_id_hex(key_id: &KeyId) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+impl MinisignPublicKey {
+    pub fn from_key(key: VerifyingKey) -> Self {
+        Self {
+            key_id: key_id(&key),
+            key,
+        }
+    }
+}
+
+impl MinisignSecretKey {
+    /// Whether `text` looks like a secret key file rather than a public key;
+    /// the comments vary between implementations, the length does not.
+    pub fn is_secret_key(text: &str) -> bool {
+        decode_box(text, "secret key").is_ok_and(|data| data.len() == SECRET_KEY_SIZE)
+    }
+
+    pub fn from_key(key: SigningKey) -> Self {
+        Self {
+            key_id: key_id(&key.verifying_key()),
+            key,
+        }
+    }
+
+    /// Parse a secret key file; `passphrase` is needed unless it was created
+    /// without one (`minisign -G -W`).
+    pub fn decode(text: &str, passphrase: Option<&[u8]>) -> Result<Self> {
+        let mut data = decode_box(text, "secret key")?;
+        if data.len() != SECRET_KEY_SIZE {
+            return Err(anyhow!("invalid minisign secret key: wrong length"));
+        }
+        if &data[..2] != SIGALG || &data[4..6] != CHK_BLAKE2B {
+            return Err(anyhow!("unsupported minisign secret key algorithm"));
+        }
+
+        let (header, keynum) = data.split_at_mut(54);
+        match (&header[2..4], passphrase) {
+            (kdf, _) if kdf == KDF_NONE => {}
+            (kdf, Some(passphrase)) if kdf == KDF_SCRYPT => {
+                let opslimit = u64::from_le_bytes(header[38..46].try_into()?);
+                let memlimit = u64::from_le_bytes(header[46..54].try_into()?);
+                let mut stream = [0u8; KEY_ID_SIZE + 64 + 32];
+                let params = scrypt_params(opslimit, memlimit)?;
+                scrypt::scrypt(passphrase, &header[6..38], &params, &mut stream)
+                    .map_err(|e| anyhow!(e.to_string()))?;
+                keynum.iter_mut().zip(stream).for_each(|(b, s)| *b ^= s);
+            }
+            (kdf, None) if kdf == KDF_SCRYPT => {
+                return Err(anyhow!(
+                    "the minisign secret key is encrypted, supply its passphrase"
+                ))
+            }
+            _ => return Err(anyhow!("unsupported minisign secret key kdf")),
+        }
+
+        let (key_id, rest) = keynum.split_at(KEY_ID_SIZE);
+        let (secret, checksum) = rest.split_at(64);
+        let expected = Blake2b::<U32>::new()
+            .chain_update(SIGALG)
+            .chain_update(key_id)
+            .chain_update(secret)
+            .finalize();
+        if expected.as_slice() != checksum {
+            return Err(anyhow!("wrong passphrase or corrupted minisign secret key"));
+        }
+        let key = SigningKey::from_bytes(secret[..32].try_into()?);
+        if key.verifying_key().as_bytes() != &secret[32..] {
+            return Err(anyhow!("invalid minisign secret key: public key mismatch"));
+        }
+        Ok(Self {
+            key_id: key_id.try_into()?,
+            key,
+        })
+    }
+
+    pub fn public_key(&self) -> MinisignPublicKey {
+        MinisignPublicKey {
+            key_id: self.key_id,
+            key: self.key.verifying_key(),
+        }
+    }
+}
+
+// libsodium's crypto_pwhash_scryptsalsa208sha256 picks N, r and p from its
+// opslimit / memlimit, and minisign stores those
+fn scrypt_params(opslimit: u64, memlimit: u64) -> Result<scrypt::Params> {
+    if memlimit > SCRYPT_MEMLIMIT_MAX {
+        return Err(anyhow!("minisign secret key: scrypt parameters too high"));
+    }
+    let opslimit = opslimit.max(32768);
+    let r = 8u32;
+    let max_n = if opslimit < memlimit / 32 {
+        opslimit / (r as u64 * 4)
+    } else {
+        memlimit / (r as u64 * 128)
+    };
+    let mut log_n = 1u8;
+    while log_n < 63 && 1u64 << log_n <= max_n / 2 {
+        log_n += 1;
+    }
+    let p = if opslimit < memlimit / 32 {
+        1
+    } else {
+        let max_rp = ((opslimit / 4) / (1u64 << log_n)).min(0x3fff_ffff) as u32;
+        max_rp / r
+    };
+    if log_n > SCRYPT_LOG_N_MAX {
+        return Err(anyhow!("minisign secret key: scrypt parameters too high"));
+    }
+    scrypt::Params::new(log_n, r, p, 32).map_err(|e| anyhow!(e.to_string()))
+}
+
+impl MinisignSignature {
+    pub fn key_id_hex(&self) -> String {
+        key_id_hex(&self.key_id)
+    }
+}
+
+/// Sign `reader` the way `minisign -S` does: Ed25519 over the BLAKE2b-512 of
+/// the message, then over that signature and the trusted comment.
+pub fn process_minisign_sign(
+    reader: &mut dyn Read,
+    key: &MinisignSecretKey,
+    trusted_comment: &str,
+    untrusted_comment: &str,
+) -> Result<MinisignSignature> {
+    if trusted_comment.contains(['\r', '\n']) || untrusted_comment.contains(['\r', '\n']) {
+        return Err(anyhow!("minisign comments must be a single line"));
+    }
+    let signer = Ed25519Signer::new(&key.key.to_bytes());
+    let digest = blake2b512(reader)?;
+    let signature = load_signature(&signer.sign(&mut digest.as_slice())?)?;
+    let global = [&signature.to_bytes(), trusted_comment.as_bytes()].concat();
+    let global_signature = load_signature(&signer.sign(&mut global.as_slice())?)?;
+    Ok(MinisignSignature {
+        prehashed: true,
+        key_id: key.key_id,
+        signature,
+        untrusted_comment: untrusted_comment.to_string(),
+        trusted_comment: trusted_comment.to_string(),
+        global_signature,
+    })
+}
+
+/// Check both signatures of a `.minisig`. Legacy (not prehashed) signatures
+/// are accepted; a key ID mismatch is an error rather than `false`.
+pub fn process_minisign_verify(
+    reader: &mut dyn Read,
+    key: &MinisignPublicKey,
+    sig: &MinisignSignature,
+) -> Result<bool> {
+    if sig.key_id != key.key_id {
+        return Err(anyhow!(
+            "the signature was made with key {}, this is key {}",
+            sig.key_id_hex(),
+            key_id_hex(&key.key_id)
+        ));
+    }
+    let verifier = Ed25519Verifier::try_new(key.key.as_bytes())?;
+    let signature = sig.signature.to_bytes();
+    let verified = if sig.prehashed {
+        verifier.verify(&mut blake2b512(reader)?.as_slice(), &signature)?
+    } else {
+        verifier.verify(reader, &signature)?
+    };
+    let global = [&signature, sig.trusted_comment.as_bytes()].concat();
+    let global_verified =
+        verifier.verify(&mut global.as_slice(), &sig.global_signature.to_bytes())?;
+    Ok(verified && global_verified)
+}
+
+/// The default trusted comment of `minisign -S`.
+pub fn default_trusted_comment(file_name: Option<&str>) -> Result<String> {
+    let timestamp = current_timestamp()?;
+    let comment = match file_name {
+        Some(name) => format!("timestamp:{}\tfile:{}\thashed", timestamp, name),
+        None => format!("timestamp:{}\thashed", timestamp),
+    };
+    Ok(comment)
+}
+
+fn blake2b512(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut hasher = Blake2b512::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+// an untrusted comment line followed by a base64 line
+fn decode_box(text: &str, what: &str) -> Result<Vec<u8>> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines
+        .next()
+        .ok_or_else(|| anyhow!("empty minisign {}", what))?;
+    let encoded = if first.starts_with(UNTRUSTED_PREFIX) {
+        lines
+            .next()
+            .ok_or_else(|| anyhow!("invalid minisign {}: missing key", what))?
+    } else {
+        first
+    };
+    STANDARD
+        .decode(encoded)
+        .map_err(|e| anyhow!("invalid minisign {}: {}", what, e))
+}
+
+impl FromStr for MinisignPublicKey {
+    type Err = anyhow::Error;
+
+    /// A public key file, or just its base64 line as `minisign -P` takes it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = decode_box(s, "public key")?;
+        if data.len() != 2 + KEY_ID_SIZE + 32 {
+            return Err(anyhow!("invalid minisign public key: wrong length"));
+        }
+        if &data[..2] != SIGALG {
+            return Err(anyhow!("unsupported minisign public key algorithm"));
+        }
+        let key = VerifyingKey::from_bytes(data[10..].try_into()?)
+            .map_err(|_| anyhow!("invalid minisign public key: not a valid curve point"))?;
+        Ok(Self {
+            key_id: data[2..10].try_into()?,
+            key,
+        })
+    }
+}
+
+impl Display for MinisignPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = [SIGALG.as_slice(), &self.key_id, self.key.as_bytes()].concat();
+        writeln!(
+            f,
+            "{}minisign public key {}",
+            UNTRUSTED_PREFIX,
+            key_id_hex(&self.key_id)
+        )?;
+        writeln!(f, "{}", STANDARD.encode(data))
+    }
+}
+
+impl Display for MinisignSecretKey {
+    /// Written unencrypted, like `minisign -G -W`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secret = [
+            self.key.as_bytes().as_slice(),
+            self.key.verifying_key().as_bytes(),
+        ]
+        .concat();
+        let checksum = Blake2b::<U32>::new()
+            .chain_update(SIGALG)
+            .chain_update(self.key_id)
+            .chain_update(&secret)
+            .finalize();
+        let data = [
+            SIGALG.as_slice(),
+            KDF_NONE,
+            CHK_BLAKE2B,
+            &[0; 32 + 8 + 8],
+            &self.key_id,
+            &secret,
+            &checksum,
+        ]
+        .concat();
+        writeln!(f, "{}minisign secret key", UNTRUSTED_PREFIX)?;
+        writeln!(f, "{}", STANDARD.encode(data))
+    }
+}
+
+impl FromStr for MinisignSignature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(|l| l.trim_end_matches('\r'));
+        let mut next = |what: &str| {
+            lines
+                .next()
+                .ok_or_else(|| anyhow!("invalid minisign signature: missing {}", what))
+        };
+        let untrusted_comment = next("untrusted comment")?
+            .strip_prefix(UNTRUSTED_PREFIX)
+            .ok_or_else(|| anyhow!("invalid minisign signature: no untrusted comment"))?
+            .to_string();
+        let data = STANDARD
+            .decode(next("signature")?.trim())
+            .map_err(|e| anyhow!("invalid minisign signature: {}", e))?;
+        let trusted_comment = next("trusted comment")?
+            .strip_prefix(TRUSTED_PREFIX)
+            .ok_or_else(|| anyhow!("invalid minisign signature: no trusted comment"))?
+            .to_string();
+        let global = STANDARD
+            .decode(next("global signature")?.trim())
+            .map_err(|e| anyhow!("invalid minisign signature: {}", e))?;
+
+        if data.len() != 2 + KEY_ID_SIZE + 64 {
+            return Err(anyhow!("invalid minisign signature: wrong length"));
+        }
+        let prehashed = match &data[..2] {
+            alg if alg == SIGALG => false,
+            alg if alg == SIGALG_PREHASHED => true,
+            _ => return Err(anyhow!("unsupported minisign signature algorithm")),
+        };
+        Ok(Self {
+            prehashed,
+            key_id: data[2..10].try_into()?,
+            signature: load_signature(&data[10..])?,
+            untrusted_comment,
+            trusted_comment,
+            global_signature: load_signature(&global)?,
+        })
+    }
+}
+
+impl Display for MinisignSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alg = if self.prehashed {
+            SIGALG_PREHASHED
+        } else {
+            SIGALG
+        };
+        let data = [alg.as_slice(), &self.key_id, &self.signature.to_bytes()].concat();
+        writeln!(f, "{}{}", UNTRUSTED_PREFIX, self.untrusted_comment)?;
+        writeln!(f, "{}", STANDARD.encode(data))?;
+        writeln!(f, "{}{}", TRUSTED_PREFIX, self.trusted_comment)?;
+        writeln!(f, "{}", STANDARD.encode(self.global_signature.to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made with the reference implementation, passphrase "rcli"
+    const PUBLIC_KEY: &str = include_str!("../../fixtures/minisign.pub");
+    const SECRET_KEY: &str = include_str!("../../fixtures/minisign.key");
+    const SIGNATURE: &str = include_str!("../../fixtures/b64.txt.minisig");
+    const MSG: &[u8] = include_bytes!("../../fixtures/b64.txt");
+
+    #[test]
+    fn t_minisign_reference() -> Result<()> {
+        let key: MinisignPublicKey = PUBLIC_KEY.parse()?;
+        assert_eq!(key_id_hex(&key.key_id), "CBDFDAD13BCEA27B");
+        let bare = PUBLIC_KEY.lines().nth(1).unwrap();
+        assert_eq!(bare.parse::<MinisignPublicKey>()?, key);
+
+        let sig: MinisignSignature = SIGNATURE.parse()?;
+        assert!(sig.prehashed);
+        assert_eq!(sig.trusted_comment, "reference trusted");
+        assert_eq!(sig.to_string(), SIGNATURE);
+        assert!(process_minisign_verify(&mut &MSG[..], &key, &sig)?);
+        assert!(!process_minisign_verify(&mut &MSG[1..], &key, &sig)?);
+
+        let mut forged = sig.clone();
+        forged.trusted_comment = "forged".to_string();
+        assert!(!process_minisign_verify(&mut &MSG[..], &key, &forged)?);
+        Ok(())
+    }
+
+    #[test]
+    fn t_minisign_secret_key() -> Result<()> {
+        assert!(MinisignSecretKey::is_secret_key(SECRET_KEY));
+        assert!(!MinisignSecretKey::is_secret_key(PUBLIC_KEY));
+        assert!(MinisignSecretKey::decode(SECRET_KEY, None).is_err());
+        assert!(MinisignSecretKey::decode(SECRET_KEY, Some(b"wrong")).is_err());
+
+        let key = MinisignSecretKey::decode(SECRET_KEY, Some(b"rcli"))?;
+        let public: MinisignPublicKey = PUBLIC_KEY.parse()?;
+        assert_eq!(key.public_key(), public);
+
+        let sig = process_minisign_sign(&mut &MSG[..], &key, "release", "untrusted")?;
+        let sig: MinisignSignature = sig.to_string().parse()?;
+        assert!(process_minisign_verify(&mut &MSG[..], &public, &sig)?);
+        assert!(process_minisign_sign(&mut &MSG[..], &key, "two\nlines", "").is_err());
+
+        // written unencrypted, read back without a passphrase
+        let plain = MinisignSecretKey::decode(&key.to_string(), None)?;
+        assert_eq!(plain.key_id, key.key_id);
+        assert_eq!(plain.key.to_bytes(), key.key.to_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn t_minisign_legacy_and_key_id() -> Result<()> {
+        let key = MinisignSecretKey::from_key(SigningKey::from_bytes(&[7; 32]));
+        let public = key.public_key();
+        assert_eq!(public.key_id, key_id(&public.key));
+
+        // "Ed" signatures cover the message itself
+        let signer = Ed25519Signer::new(&key.key.to_bytes());
+        let signature = load_signature(&signer.sign(&mut &MSG[..])?)?;
+        let global = [&signature.to_bytes(), b"legacy".as_slice()].concat();
+        let sig = MinisignSignature {
+            prehashed: false,
+            key_id: key.key_id,
+            signature,
+            untrusted_comment: String::new(),
+            trusted_comment: "legacy".to_string(),
+            global_signature: load_signature(&signer.sign(&mut global.as_slice())?)?,
+        };
+        let sig: MinisignSignature = sig.to_string().parse()?;
+        assert!(!sig.prehashed);
+        assert!(process_minisign_verify(&mut &MSG[..], &public, &sig)?);
+
+        let other: MinisignPublicKey = PUBLIC_KEY.parse()?;
+        let err = process_minisign_verify(&mut &MSG[..], &other, &sig).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("the signature was made with key"));
+        Ok(())
+    }
+}
@@ -1,6 +1,17 @@
+pub mod age;
 pub mod base64;
+pub mod container;
 pub mod csv;
+pub mod datauri;
+pub mod envelope;
 pub mod genpass;
+pub mod hash;
 pub mod http;
 pub mod jwt;
+pub mod keyfile;
+pub mod keyring;
+pub mod minisign;
+pub mod otp;
+pub mod secret;
 pub mod text;
+pub mod webhook;
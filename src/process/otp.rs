@@ -0,0 +1,279 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, Ok, Result};
+use data_encoding::{Encoding, Specification, BASE32_NOPAD};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+use crate::cli::otp::OtpAlgorithm;
+
+/// Widest `--window` accepted; every candidate costs an HMAC.
+pub const MAX_OTP_WINDOW: u64 = 100;
+
+/// Decode a base32 secret the way authenticator apps accept them: any case,
+/// optional padding, and spaces or dashes between groups.
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let cleaned: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if cleaned.is_empty() {
+        return Err(anyhow!("otp secret is empty"));
+    }
+    let secret = lenient_base32()
+        .decode(cleaned.as_bytes())
+        .map_err(|e| anyhow!("invalid base32 secret: {}", e))?;
+    Ok(secret)
+}
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+fn lenient_base32() -> Encoding {
+    let mut spec = Specification::new();
+    spec.symbols.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ234567");
+    spec.check_trailing_bits = false;
+    spec.encoding().unwrap()
+}
+
+// RFC 4226 section 5.3
+pub fn process_hotp(
+    secret: &[u8],
+    counter: u64,
+    digits: u32,
+    algorithm: OtpAlgorithm,
+) -> Result<String> {
+    let msg = counter.to_be_bytes();
+    let hash = match algorithm {
+        OtpAlgorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(secret, &msg)?,
+        OtpAlgorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(secret, &msg)?,
+        OtpAlgorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(secret, &msg)?,
+    };
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into()?) & 0x7fff_ffff;
+    let code = binary as u64 % 10u64.pow(digits);
+    Ok(format!("{:0width$}", code, width = digits as usize))
+}
+
+// RFC 6238 section 4.2, with T0 = 0
+pub fn process_totp(
+    secret: &[u8],
+    timestamp: u64,
+    period: u64,
+    digits: u32,
+    algorithm: OtpAlgorithm,
+) -> Result<String> {
+    process_hotp(secret, totp_step(timestamp, period)?, digits, algorithm)
+}
+
+pub fn totp_step(timestamp: u64, period: u64) -> Result<u64> {
+    if period == 0 {
+        return Err(anyhow!("totp period must be greater than 0"));
+    }
+    Ok(timestamp / period)
+}
+
+/// Check `code` against every counter in `counters` and return the one that
+/// matched. All candidates are computed so the time taken does not depend on
+/// which one matches.
+pub fn process_otp_verify(
+    secret: &[u8],
+    code: &str,
+    counters: RangeInclusive<u64>,
+    digits: u32,
+    algorithm: OtpAlgorithm,
+) -> Result<Option<u64>> {
+    if counters.end().saturating_sub(*counters.start()) > 2 * MAX_OTP_WINDOW {
+        return Err(anyhow!(
+            "the window is too wide, at most {} steps are checked each way",
+            MAX_OTP_WINDOW
+        ));
+    }
+    let mut matched = None;
+    for candidate in counters {
+        let expected = process_hotp(secret, candidate, digits, algorithm)?;
+        if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) && matched.is_none() {
+            matched = Some(candidate);
+        }
+    }
+    Ok(matched)
+}
+
+pub fn process_otp_secret_generate(len: usize) -> String {
+    let mut secret = vec![0u8; len];
+    OsRng.fill_bytes(&mut secret);
+    encode_secret(&secret)
+}
+
+pub struct OtpUri<'a> {
+    pub secret: &'a [u8],
+    pub issuer: Option<&'a str>,
+    pub account: &'a str,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    /// `None` for TOTP, the initial counter for HOTP.
+    pub counter: Option<u64>,
+    pub period: u64,
+}
+
+/// Key URI format understood by authenticator apps:
+/// otpauth://TYPE/ISSUER:ACCOUNT?secret=...&issuer=...
+pub fn process_otp_uri(uri: &OtpUri) -> String {
+    let kind = if uri.counter.is_some() {
+        "hotp"
+    } else {
+        "totp"
+    };
+    let label = match uri.issuer {
+        Some(issuer) => format!("{}:{}", percent_encode(issuer), percent_encode(uri.account)),
+        None => percent_encode(uri.account),
+    };
+
+    let mut query = format!("secret={}", encode_secret(uri.secret));
+    if let Some(issuer) = uri.issuer {
+        query.push_str(&format!("&issuer={}", percent_encode(issuer)));
+    }
+    query.push_str(&format!(
+        "&algorithm={}&digits={}",
+        uri.algorithm.to_string().to_uppercase(),
+        uri.digits
+    ));
+    match uri.counter {
+        Some(counter) => query.push_str(&format!("&counter={}", counter)),
+        None => query.push_str(&format!("&period={}", uri.period)),
+    }
+
+    format!("otpauth://{}/{}?{}", kind, label, query)
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(key: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(key)?;
+    mac.update(msg);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::otp::OtpVerifyOpts;
+    use clap::Parser;
+
+    const SEED_SHA1: &[u8] = b"12345678901234567890";
+    const SEED_SHA256: &[u8] = b"12345678901234567890123456789012";
+    const SEED_SHA512: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    #[test]
+    fn t_hotp_rfc4226() -> Result<()> {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            let ret = process_hotp(SEED_SHA1, counter as u64, 6, OtpAlgorithm::Sha1)?;
+            assert_eq!(&ret, code);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_totp_rfc6238() -> Result<()> {
+        let cases = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1, sha256, sha512) in cases {
+            assert_eq!(
+                process_totp(SEED_SHA1, time, 30, 8, OtpAlgorithm::Sha1)?,
+                sha1
+            );
+            assert_eq!(
+                process_totp(SEED_SHA256, time, 30, 8, OtpAlgorithm::Sha256)?,
+                sha256
+            );
+            assert_eq!(
+                process_totp(SEED_SHA512, time, 30, 8, OtpAlgorithm::Sha512)?,
+                sha512
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_otp_verify_window() -> Result<()> {
+        let code = process_hotp(SEED_SHA1, 10, 6, OtpAlgorithm::Sha1)?;
+        let ret = process_otp_verify(SEED_SHA1, &code, 9..=11, 6, OtpAlgorithm::Sha1)?;
+        assert_eq!(ret, Some(10));
+        let ret = process_otp_verify(SEED_SHA1, &code, 7..=9, 6, OtpAlgorithm::Sha1)?;
+        assert_eq!(ret, None);
+        let ret = process_otp_verify(SEED_SHA1, &code, 11..=20, 6, OtpAlgorithm::Sha1)?;
+        assert_eq!(ret, None);
+
+        // a huge window would walk billions of HMACs
+        assert!(process_otp_verify(SEED_SHA1, &code, 0..=u64::MAX, 6, OtpAlgorithm::Sha1).is_err());
+        let parse = |window: &str| {
+            let args = ["verify", "-s", "GEZDGNBV", "--code", "123456", "-w", window];
+            OtpVerifyOpts::try_parse_from(args).map(|opts| opts.window)
+        };
+        assert_eq!(parse("100").unwrap(), MAX_OTP_WINDOW);
+        assert!(parse("101").is_err());
+        assert!(parse("18446744073709551615").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_secret_encoding() -> Result<()> {
+        let encoded = encode_secret(SEED_SHA1);
+        assert_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            decode_secret("gezd gnbv gy3t qojq-GEZDGNBVGY3TQOJQ")?,
+            SEED_SHA1
+        );
+        assert_eq!(
+            decode_secret("JBSWY3DPEHPK3PXP")?,
+            b"Hello!\xde\xad\xbe\xef"
+        );
+        assert!(decode_secret("not base32!").is_err());
+        assert!(decode_secret("  ").is_err());
+
+        let generated = process_otp_secret_generate(20);
+        assert_eq!(decode_secret(&generated)?.len(), 20);
+        Ok(())
+    }
+
+    #[test]
+    fn t_otp_uri() {
+        let uri = OtpUri {
+            secret: b"Hello!\xde\xad\xbe\xef",
+            issuer: Some("ACME Co"),
+            account: "john@example.com",
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            counter: None,
+            period: 30,
+        };
+        assert_eq!(
+            process_otp_uri(&uri),
+            "otpauth://totp/ACME%20Co:john%40example.com?secret=JBSWY3DPEHPK3PXP\
+             &issuer=ACME%20Co&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
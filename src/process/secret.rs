@@ -0,0 +1,283 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Ok, Result};
+use bech32::{primitives::decode::CheckedHrpstring, Bech32m, Hrp};
+use rand::{rngs::OsRng, RngCore};
+
+const SHARE_HRP: &str = "rcli-share";
+const SHARE_VERSION: u8 = 1;
+// version, set ID, threshold, index
+const SHARE_HEADER_SIZE: usize = 7;
+const SET_ID_SIZE: usize = 4;
+// appended to the secret before splitting, so a wrong combination is caught
+const DIGEST_SIZE: usize = 4;
+const DIGEST_CONTEXT: &str = "rcli secret share digest v1";
+/// Keeps a share within the 1023 characters a bech32m checksum covers.
+pub const MAX_SECRET_SIZE: usize = 512;
+
+/// One share of a secret split with Shamir's scheme over GF(2^8). Written as
+/// a bech32m string, lowercase and checksummed so typos are caught.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Random ID shared by all shares of one split.
+    pub id: [u8; SET_ID_SIZE],
+    pub threshold: u8,
+    /// The x coordinate, 1 to 255.
+    pub index: u8,
+    value: Vec<u8>,
+}
+
+/// Split `secret` into `shares` shares, any `threshold` of which recover it.
+pub fn process_secret_split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>> {
+    if secret.is_empty() {
+        return Err(anyhow!("the secret is empty"));
+    }
+    if secret.len() > MAX_SECRET_SIZE {
+        return Err(anyhow!(
+            "the secret is {} bytes, shares hold at most {}; split a key that encrypts it instead",
+            secret.len(),
+            MAX_SECRET_SIZE
+        ));
+    }
+    if threshold < 2 || threshold > shares {
+        return Err(anyhow!(
+            "the threshold must be between 2 and the number of shares ({}), got {}",
+            shares,
+            threshold
+        ));
+    }
+
+    let mut id = [0u8; SET_ID_SIZE];
+    OsRng.fill_bytes(&mut id);
+    let mut data = secret.to_vec();
+    data.extend_from_slice(&digest(secret));
+
+    let mut values = vec![Vec::with_capacity(data.len()); shares as usize];
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in data {
+        // a random polynomial of degree threshold - 1 through (0, byte)
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+        for (i, value) in values.iter_mut().enumerate() {
+            value.push(evaluate(&coefficients, i as u8 + 1));
+        }
+    }
+    coefficients.fill(0);
+
+    let shares = values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| Share {
+            id,
+            threshold,
+            index: i as u8 + 1,
+            value,
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Recover the secret from at least `threshold` shares of the same split.
+pub fn process_secret_combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let first = shares.first().ok_or_else(|| anyhow!("no shares given"))?;
+    let mut points: Vec<&Share> = Vec::new();
+    for share in shares {
+        if share.id != first.id
+            || share.threshold != first.threshold
+            || share.value.len() != first.value.len()
+        {
+            return Err(anyhow!("the shares come from different splits"));
+        }
+        match points.iter().find(|point| point.index == share.index) {
+            Some(point) if point.value != share.value => {
+                return Err(anyhow!("two different shares have index {}", share.index));
+            }
+            Some(_) => {}
+            None => points.push(share),
+        }
+    }
+    if points.len() < first.threshold as usize {
+        return Err(anyhow!(
+            "{} shares are needed, got {}",
+            first.threshold,
+            points.len()
+        ));
+    }
+    let points = &points[..first.threshold as usize];
+
+    // Lagrange interpolation at x = 0; subtraction is xor in GF(2^8)
+    let mut data = vec![0u8; first.value.len()];
+    for (i, share) in points.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, other) in points.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+            }
+        }
+        for (byte, y) in data.iter_mut().zip(&share.value) {
+            *byte ^= gf_mul(basis, *y);
+        }
+    }
+
+    let secret_size = data.len() - DIGEST_SIZE;
+    if digest(&data[..secret_size]) != data[secret_size..] {
+        return Err(anyhow!(
+            "the shares do not combine to the secret, one is wrong"
+        ));
+    }
+    data.truncate(secret_size);
+    Ok(data)
+}
+
+fn digest(secret: &[u8]) -> [u8; DIGEST_SIZE] {
+    let hash = blake3::derive_key(DIGEST_CONTEXT, secret);
+    hash[..DIGEST_SIZE].try_into().unwrap()
+}
+
+// Horner's rule
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |acc, coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+// multiplication modulo x^8 + x^4 + x^3 + x + 1 (AES), without branches or
+// tables so the timing does not depend on the secret
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+// a * b^254, where b^254 = b^-1 for b != 0
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    let mut power = b;
+    for _ in 0..7 {
+        power = gf_mul(power, power);
+        inverse = gf_mul(inverse, power);
+    }
+    gf_mul(a, inverse)
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut data = Vec::with_capacity(SHARE_HEADER_SIZE + self.value.len());
+        data.push(SHARE_VERSION);
+        data.extend_from_slice(&self.id);
+        data.push(self.threshold);
+        data.push(self.index);
+        data.extend_from_slice(&self.value);
+        let hrp = Hrp::parse(SHARE_HRP).expect("valid hrp");
+        let share = bech32::encode::<Bech32m>(hrp, &data).map_err(|_| fmt::Error)?;
+        write!(f, "{}", share)
+    }
+}
+
+impl FromStr for Share {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let checked = CheckedHrpstring::new::<Bech32m>(s)
+            .map_err(|e| anyhow!("invalid share, check it for typos: {}", e))?;
+        if !checked.hrp().as_str().eq_ignore_ascii_case(SHARE_HRP) {
+            return Err(anyhow!("expected a \"{}1...\" share", SHARE_HRP));
+        }
+        let data: Vec<u8> = checked.byte_iter().collect();
+        if data.len() <= SHARE_HEADER_SIZE + DIGEST_SIZE {
+            return Err(anyhow!("invalid share: too short"));
+        }
+        if data[0] != SHARE_VERSION {
+            return Err(anyhow!("unsupported share version {}", data[0]));
+        }
+        let share = Share {
+            id: data[1..1 + SET_ID_SIZE].try_into().unwrap(),
+            threshold: data[5],
+            index: data[6],
+            value: data[SHARE_HEADER_SIZE..].to_vec(),
+        };
+        if share.index == 0 || share.threshold < 2 {
+            return Err(anyhow!(
+                "invalid share: index {}, threshold {}",
+                share.index,
+                share.threshold
+            ));
+        }
+        Ok(share)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_gf256() {
+        // the examples in FIPS 197 section 4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_div(1, a)), 1);
+        }
+    }
+
+    #[test]
+    fn t_secret_split_combine() -> Result<()> {
+        let secret = b"correct horse battery staple";
+        let shares = process_secret_split(secret, 3, 5)?;
+        assert_eq!(shares.len(), 5);
+        // every 3-subset recovers the secret
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let subset = [shares[a].clone(), shares[c].clone(), shares[b].clone()];
+                    assert_eq!(process_secret_combine(&subset)?, secret);
+                }
+            }
+        }
+        // duplicates don't count
+        let twice = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(process_secret_combine(&twice).is_err());
+
+        // a share from another split is noticed
+        let other = process_secret_split(secret, 3, 5)?;
+        let mixed = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert!(process_secret_combine(&mixed).is_err());
+        let mut wrong = shares[2].clone();
+        wrong.value[0] ^= 1;
+        let err = process_secret_combine(&[shares[0].clone(), shares[1].clone(), wrong]);
+        assert!(err.is_err());
+
+        assert!(process_secret_split(secret, 1, 5).is_err());
+        assert!(process_secret_split(secret, 6, 5).is_err());
+        assert!(process_secret_split(&[0; MAX_SECRET_SIZE + 1], 2, 2).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_share_encoding() -> Result<()> {
+        let shares = process_secret_split(&[0xab; 32], 2, 3)?;
+        let text = shares[1].to_string();
+        assert!(text.starts_with("rcli-share1"));
+        assert_eq!(text.parse::<Share>()?, shares[1]);
+        assert_eq!(text.to_uppercase().parse::<Share>()?, shares[1]);
+
+        // any single typo is caught by the checksum
+        let typo = |i: usize| {
+            let mut chars: Vec<char> = text.chars().collect();
+            chars[i] = if chars[i] == 'q' { 'p' } else { 'q' };
+            chars.into_iter().collect::<String>()
+        };
+        for i in SHARE_HRP.len() + 1..text.len() {
+            assert!(typo(i).parse::<Share>().is_err());
+        }
+        Ok(())
+    }
+}
@@ -1,61 +1,199 @@
 use anyhow::{anyhow, Ok, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
 
+use aes_gcm::Aes256Gcm;
+use aes_gcm_siv::Aes256GcmSiv;
+use argon2::{Algorithm, Argon2, Params, Version};
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng as ChaOsRng},
-    ChaCha20Poly1305, Error as ChaError, KeySizeUser, Nonce,
+    aead::{generic_array::GenericArray, Aead, AeadCore, KeyInit, OsRng as ChaOsRng, Payload},
+    ChaCha20Poly1305, Error as ChaError, KeySizeUser, Nonce, XChaCha20Poly1305,
 };
 use core::result::Result as CoreResult;
 use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
-use rand::rngs::OsRng;
-use std::{collections::HashMap, io::Read};
-
-use crate::cli::text::TextSignMethod;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
 
-use super::genpass::process_genpass;
+use super::{
+    age::AgeIdentity,
+    container::{process_container_decrypt, Secret, CONTAINER_MAGIC},
+    keyfile::Ed25519Key,
+};
+use crate::{
+    cli::text::{KeyEncoding, TextCipher, TextKeyMethod, TextSignMethod},
+    utils::{read_full, trim_newline},
+};
 
 pub type KeyOutput = HashMap<&'static str, Vec<u8>>;
 
+pub const STREAM_SEGMENT_SIZE: usize = 64 * 1024;
+const STREAM_TAG_SIZE: usize = 16;
+
+// refuse headers asking for more than 4 GiB, a corrupted file should not OOM
+const KDF_MAX_M_COST: u32 = 4 * 1024 * 1024;
+/// Argon2id cost of `genpass derive` and the passphrase default: 64 MiB,
+/// 3 passes, 1 lane. Derived passwords depend on it.
+pub const ARGON2_M_COST: u32 = 64 * 1024;
+pub const ARGON2_T_COST: u32 = 3;
+pub const ARGON2_P_COST: u32 = 1;
+
 pub trait TextSigner {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>>;
+
+    fn sign_file(&self, path: &Path) -> Result<Vec<u8>> {
+        self.sign(&mut File::open(path)?)
+    }
 }
 
 pub trait TextVerifier {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool>;
+
+    fn verify_file(&self, path: &Path, sig: &[u8]) -> Result<bool> {
+        self.verify(&mut File::open(path)?, sig)
+    }
+}
+
+/// How files are fed to the hasher. Only BLAKE3 can make use of these, the
+/// other methods always stream the file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashOptions {
+    /// Memory-map the file instead of reading it.
+    pub mmap: bool,
+    /// Memory-map the file and hash it on all cores.
+    pub parallel: bool,
+}
+
+/// Argon2id cost, recorded in the container header of passphrase encrypted
+/// data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory in KiB.
+    pub m_cost: u32,
+    /// Number of passes.
+    pub t_cost: u32,
+    /// Degree of parallelism.
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: ARGON2_M_COST,
+            t_cost: ARGON2_T_COST,
+            p_cost: ARGON2_P_COST,
+        }
+    }
+}
+
+/// An AEAD picked at runtime, all with 256-bit keys and 128-bit tags.
+pub enum AeadCipher {
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    XChaCha20Poly1305(XChaCha20Poly1305),
+    Aes256Gcm(Box<Aes256Gcm>),
+    Aes256GcmSiv(Box<Aes256GcmSiv>),
 }
 
 pub struct Blake3 {
     key: [u8; 32],
+    options: HashOptions,
+}
+
+/// HMAC (RFC 2104) over SHA-256 or SHA-512, the keyed MAC most webhooks and
+/// APIs use. Keys are shared secrets of any length.
+#[derive(Clone)]
+pub enum TextHmac {
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
 }
 
+pub struct ChaChaKey;
+
 pub struct Ed25519Signer {
     key: ed25519_dalek::SigningKey,
+    prehashed: bool,
 }
 
 pub struct Ed25519Verifier {
     key: ed25519_dalek::VerifyingKey,
+    prehashed: bool,
 }
 
 impl TextSigner for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let hash = blake3::keyed_hash(&self.key, &buf);
-        // cloned
-        Ok(hash.as_bytes().to_vec())
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update_reader(reader)?;
+        Ok(hasher.finalize().as_bytes().to_vec())
+    }
+
+    fn sign_file(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(self.hash_file(path)?.as_bytes().to_vec())
     }
 }
 
 impl TextVerifier for Blake3 {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let hash = blake3::keyed_hash(&self.key, &buf);
-        Ok(hash.as_bytes() == sig)
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update_reader(reader)?;
+        // blake3::Hash compares in constant time
+        Ok(hasher.finalize() == *sig)
+    }
+
+    fn verify_file(&self, path: &Path, sig: &[u8]) -> Result<bool> {
+        Ok(self.hash_file(path)? == *sig)
+    }
+}
+
+impl TextSigner for TextHmac {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let tag = match self.clone() {
+            Self::Sha256(mut mac) => {
+                io::copy(reader, &mut mac)?;
+                mac.finalize().into_bytes().to_vec()
+            }
+            Self::Sha512(mut mac) => {
+                io::copy(reader, &mut mac)?;
+                mac.finalize().into_bytes().to_vec()
+            }
+        };
+        Ok(tag)
+    }
+}
+
+impl TextVerifier for TextHmac {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        // verify_slice compares in constant time
+        let verified = match self.clone() {
+            Self::Sha256(mut mac) => {
+                io::copy(reader, &mut mac)?;
+                mac.verify_slice(sig).is_ok()
+            }
+            Self::Sha512(mut mac) => {
+                io::copy(reader, &mut mac)?;
+                mac.verify_slice(sig).is_ok()
+            }
+        };
+        Ok(verified)
     }
 }
 
 impl TextSigner for Ed25519Signer {
     fn sign(&self, msg: &mut dyn Read) -> Result<Vec<u8>> {
+        if self.prehashed {
+            let mut hasher = Sha512::new();
+            io::copy(msg, &mut hasher)?;
+            let sig = self.key.sign_prehashed(hasher, None)?;
+            return Ok(sig.to_bytes().to_vec());
+        }
+
         let mut buf = Vec::new();
         msg.read_to_end(&mut buf)?;
         // using trait function must include it
@@ -66,40 +204,106 @@ impl TextSigner for Ed25519Signer {
 
 impl TextVerifier for Ed25519Verifier {
     fn verify(&self, msg: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        let sig = load_signature(sig)?;
+        if self.prehashed {
+            let mut hasher = Sha512::new();
+            io::copy(msg, &mut hasher)?;
+            return Ok(self.key.verify_prehashed_strict(hasher, None, &sig).is_ok());
+        }
+
         let mut buf = Vec::new();
         msg.read_to_end(&mut buf)?;
-        // NOTE
-        let sig = (&sig[..64]).try_into()?;
-        let sig = Signature::from_bytes(sig);
         Ok(self.key.verify_strict(&buf, &sig).is_ok())
     }
 }
 
 impl Blake3 {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        Ok(Self { key })
+        Ok(Self {
+            key: load_key(key.as_ref(), "blake3")?,
+            options: HashOptions::default(),
+        })
+    }
+
+    pub fn with_options(mut self, options: HashOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn hash_file(&self, path: &Path) -> Result<blake3::Hash> {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        // update_mmap falls back to reading for small files and pipes
+        if self.options.parallel {
+            hasher.update_mmap_rayon(path)?;
+        } else if self.options.mmap {
+            hasher.update_mmap(path)?;
+        } else {
+            hasher.update_reader(File::open(path)?)?;
+        }
+        Ok(hasher.finalize())
     }
 
     fn generate() -> Result<KeyOutput> {
-        let key = process_genpass(32, true, true, true, true)?;
         let mut map = HashMap::new();
-        map.insert("blake3.txt", key.as_bytes().to_vec());
+        map.insert("blake3.key", random_key().to_vec());
         Ok(map)
     }
 }
 
+impl TextHmac {
+    pub fn sha256(key: &[u8]) -> Result<Self> {
+        let mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key(key)?)
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(Self::Sha256(mac))
+    }
+
+    pub fn sha512(key: &[u8]) -> Result<Self> {
+        let mac = <Hmac<Sha512> as Mac>::new_from_slice(hmac_key(key)?)
+            .map_err(|e| anyhow!(e.to_string()))?;
+        Ok(Self::Sha512(mac))
+    }
+}
+
+/// The secret an HMAC key file holds: the bytes as they are, except for the
+/// trailing newline `echo` and editors add.
+pub fn hmac_key(key: &[u8]) -> Result<&[u8]> {
+    let key = trim_newline(key);
+    if key.is_empty() {
+        return Err(anyhow!("invalid hmac key: the key is empty"));
+    }
+    Ok(key)
+}
+
 impl Ed25519Signer {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
         let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        Ok(Self::new(key))
+        match Ed25519Key::decode_structured(key)? {
+            Some(Ed25519Key::Signing(key)) => return Ok(Self::new(&key.to_bytes())),
+            Some(Ed25519Key::Verifying(_)) => {
+                return Err(anyhow!("expected an ed25519 private key, got a public key"))
+            }
+            None => {}
+        }
+        Ok(Self::new(&load_key(key, "ed25519 private")?))
     }
 
     pub fn new(key: &[u8; 32]) -> Self {
         let key = SigningKey::from_bytes(key);
-        Self { key }
+        Self {
+            key,
+            prehashed: false,
+        }
+    }
+
+    /// Sign the SHA-512 of the message (Ed25519ph, RFC 8032), which can be
+    /// computed while streaming.
+    pub fn prehashed(mut self) -> Self {
+        self.prehashed = true;
+        self
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
     }
 
     fn generate() -> Result<KeyOutput> {
@@ -116,62 +320,230 @@ impl Ed25519Signer {
 impl Ed25519Verifier {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
         let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        let key = VerifyingKey::from_bytes(key)?;
-        Ok(Self { key })
+        // a private key in a structured format verifies with its public half
+        if let Some(key) = Ed25519Key::decode_structured(key)? {
+            return Ok(Self {
+                key: key.verifying_key(),
+                prehashed: false,
+            });
+        }
+        let key = load_key(key, "ed25519 public")?;
+        let key = VerifyingKey::from_bytes(&key)
+            .map_err(|_| anyhow!("invalid ed25519 public key: not a valid curve point"))?;
+        Ok(Self {
+            key,
+            prehashed: false,
+        })
+    }
+
+    pub fn prehashed(mut self) -> Self {
+        self.prehashed = true;
+        self
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key
     }
 }
 
-pub fn process_text_sign(
-    msg: &mut dyn Read,
+/// Load an `N`-byte key stored raw, or as hex or base64 text the way
+/// `text generate -e` writes it. Surrounding whitespace is allowed around text;
+/// a raw key is `N` bytes, optionally followed by one newline like
+/// [`hmac_key`] accepts. `what` names the key in errors.
+pub fn load_key<const N: usize>(data: &[u8], what: &str) -> Result<[u8; N]> {
+    if data.is_empty() {
+        return Err(anyhow!("invalid {} key: the key is empty", what));
+    }
+    if data.len() == N {
+        return Ok(data.try_into()?);
+    }
+    // saved by an editor or `echo`; text of this length decodes to fewer
+    // bytes. A raw key may itself end in \r, so try a bare \n as well
+    let bare = data.strip_suffix(b"\n").unwrap_or(data);
+    if let Some(raw) = [trim_newline(data), bare]
+        .into_iter()
+        .find(|raw| raw.len() == N)
+    {
+        return Ok(raw.try_into()?);
+    }
+
+    let text = std::str::from_utf8(data).map(str::trim).unwrap_or_default();
+    let (encoding, decoded) = if text.is_empty() {
+        ("raw", None)
+    } else if text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        ("hex", hex::decode(text).ok())
+    } else {
+        let decoded = [URL_SAFE_NO_PAD, URL_SAFE, STANDARD, STANDARD_NO_PAD]
+            .iter()
+            .find_map(|engine| engine.decode(text).ok());
+        ("base64", decoded)
+    };
+    match decoded {
+        Some(bytes) if bytes.len() == N => Ok(bytes.try_into().unwrap()),
+        Some(bytes) => Err(anyhow!(
+            "invalid {} key: {} decodes to {} bytes, require {}",
+            what,
+            encoding,
+            bytes.len(),
+            N
+        )),
+        None if encoding == "hex" => Err(anyhow!(
+            "invalid {} key: odd number of hex digits ({})",
+            what,
+            text.len()
+        )),
+        None => Err(anyhow!(
+            "invalid {} key: require {} raw bytes, or hex or base64 text, got {} bytes",
+            what,
+            N,
+            data.len()
+        )),
+    }
+}
+
+/// An Ed25519 signature must be exactly 64 bytes.
+pub fn load_signature(sig: &[u8]) -> Result<Signature> {
+    let bytes: &[u8; 64] = sig.try_into().map_err(|_| {
+        anyhow!(
+            "invalid ed25519 signature: require 64 bytes, got {}",
+            sig.len()
+        )
+    })?;
+    Ok(Signature::from_bytes(bytes))
+}
+
+pub(crate) fn text_signer(
     key: &[u8],
     method: TextSignMethod,
-) -> Result<Vec<u8>> {
+    options: HashOptions,
+) -> Result<Box<dyn TextSigner>> {
     let signer: Box<dyn TextSigner> = match method {
-        TextSignMethod::Blake3 => Box::new(Blake3::try_new(key)?),
+        TextSignMethod::Blake3 => Box::new(Blake3::try_new(key)?.with_options(options)),
         TextSignMethod::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
+        TextSignMethod::Ed25519ph => Box::new(Ed25519Signer::try_new(key)?.prehashed()),
+        TextSignMethod::HmacSha256 => Box::new(TextHmac::sha256(key)?),
+        TextSignMethod::HmacSha512 => Box::new(TextHmac::sha512(key)?),
+    };
+    Ok(signer)
+}
+
+pub(crate) fn text_verifier(
+    key: &[u8],
+    method: TextSignMethod,
+    options: HashOptions,
+) -> Result<Box<dyn TextVerifier>> {
+    let verifier: Box<dyn TextVerifier> = match method {
+        TextSignMethod::Blake3 => Box::new(Blake3::try_new(key)?.with_options(options)),
+        TextSignMethod::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+        TextSignMethod::Ed25519ph => Box::new(Ed25519Verifier::try_new(key)?.prehashed()),
+        TextSignMethod::HmacSha256 => Box::new(TextHmac::sha256(key)?),
+        TextSignMethod::HmacSha512 => Box::new(TextHmac::sha512(key)?),
     };
+    Ok(verifier)
+}
 
-    signer.sign(msg)
+pub fn process_text_sign(
+    msg: &mut dyn Read,
+    key: &[u8],
+    method: TextSignMethod,
+) -> Result<Vec<u8>> {
+    text_signer(key, method, HashOptions::default())?.sign(msg)
 }
 
-// verify signature with session key (blake3) or public key (ed25519)
+pub fn process_text_sign_file(
+    path: &Path,
+    key: &[u8],
+    method: TextSignMethod,
+    options: HashOptions,
+) -> Result<Vec<u8>> {
+    text_signer(key, method, options)?.sign_file(path)
+}
+
+// verify signature with session key (blake3, hmac) or public key (ed25519)
 pub fn process_text_verify(
     msg: &mut dyn Read,
     key: &[u8],
     sig: &[u8],
     method: TextSignMethod,
 ) -> Result<bool> {
-    let verifier: Box<dyn TextVerifier> = match method {
-        TextSignMethod::Blake3 => Box::new(Blake3::try_new(key)?),
-        TextSignMethod::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
-    };
+    text_verifier(key, method, HashOptions::default())?.verify(msg, sig)
+}
 
-    verifier.verify(msg, sig)
+pub fn process_text_verify_file(
+    path: &Path,
+    key: &[u8],
+    sig: &[u8],
+    method: TextSignMethod,
+    options: HashOptions,
+) -> Result<bool> {
+    text_verifier(key, method, options)?.verify_file(path, sig)
 }
 
-pub fn process_text_key_generate(method: TextSignMethod) -> Result<KeyOutput> {
+impl ChaChaKey {
+    fn generate() -> Result<KeyOutput> {
+        let mut map = HashMap::new();
+        map.insert("chacha20poly1305.key", random_key().to_vec());
+        Ok(map)
+    }
+}
+
+// 256 bits straight from the OS CSPRNG
+fn random_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+pub fn process_text_key_generate(method: TextKeyMethod) -> Result<KeyOutput> {
     match method {
-        TextSignMethod::Blake3 => Blake3::generate(),
-        TextSignMethod::Ed25519 => Ed25519Signer::generate(),
+        TextKeyMethod::Blake3 => Blake3::generate(),
+        TextKeyMethod::Ed25519 => Ed25519Signer::generate(),
+        TextKeyMethod::ChaCha20Poly1305 => ChaChaKey::generate(),
+        TextKeyMethod::X25519 => {
+            // always in the age text format, usable with age and rage too
+            let identity = AgeIdentity::generate();
+            let mut map = HashMap::new();
+            map.insert("x25519.key", identity.to_identity_file().into_bytes());
+            map.insert(
+                "x25519.pub",
+                format!("{}\n", identity.recipient()).into_bytes(),
+            );
+            Ok(map)
+        }
     }
 }
 
-pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8], encrypt: bool) -> Result<Vec<u8>> {
+pub fn encode_key(key: &[u8], encoding: KeyEncoding) -> Result<Vec<u8>> {
+    match encoding {
+        KeyEncoding::Raw => Ok(key.to_vec()),
+        KeyEncoding::Hex => Ok(hex::encode(key).into_bytes()),
+        KeyEncoding::Base64 => Ok(URL_SAFE_NO_PAD.encode(key).into_bytes()),
+        KeyEncoding::Pem | KeyEncoding::Openssh | KeyEncoding::Minisign => {
+            Err(anyhow!("{} encoding is only for ed25519 keys", encoding))
+        }
+    }
+}
+
+/// One-shot `nonce || ciphertext`, the format before containers. `aad` is
+/// authenticated but not included in the output.
+pub fn process_text_encrypt(
+    reader: &mut dyn Read,
+    key: &[u8],
+    aad: &[u8],
+    encrypt: bool,
+) -> Result<Vec<u8>> {
     let mut text = Vec::new();
     reader.read_to_end(&mut text)?;
 
-    let chacha = ChaCha20Poly1305::new_from_slice(key).map_err(|_| {
-        anyhow!(
-            "invalid key length: require {}, got {}",
-            ChaCha20Poly1305::key_size(),
-            key.len()
-        )
-    })?;
+    let chacha = new_chacha(key)?;
 
     if encrypt {
         let nonce = ChaCha20Poly1305::generate_nonce(&mut ChaOsRng);
-        let result: CoreResult<Vec<u8>, ChaError> = chacha.encrypt(&nonce, text.as_slice());
+        let payload = Payload {
+            msg: text.as_slice(),
+            aad,
+        };
+        let result: CoreResult<Vec<u8>, ChaError> = chacha.encrypt(&nonce, payload);
         let ciphertext = result.map_err(|e| anyhow!(e.to_string()))?;
         Ok([nonce.as_slice(), &ciphertext].concat())
     } else {
@@ -184,16 +556,208 @@ pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8], encrypt: bool) ->
             ));
         }
         let nonce = Nonce::from_slice(&text[..nsize]);
-        let result: CoreResult<Vec<u8>, ChaError> = chacha.decrypt(nonce, &text[nsize..]);
+        let payload = Payload {
+            msg: &text[nsize..],
+            aad,
+        };
+        let result: CoreResult<Vec<u8>, ChaError> = chacha.decrypt(nonce, payload);
         let plaintext = result.map_err(|e| anyhow!(e.to_string()))?;
         Ok(plaintext)
     }
 }
 
+pub(crate) fn new_chacha(key: &[u8]) -> Result<ChaCha20Poly1305> {
+    ChaCha20Poly1305::new_from_slice(key).map_err(|_| {
+        anyhow!(
+            "invalid key length: require {}, got {}",
+            ChaCha20Poly1305::key_size(),
+            key.len()
+        )
+    })
+}
+
+impl AeadCipher {
+    pub fn try_new(cipher: TextCipher, key: &[u8]) -> Result<Self> {
+        if key.len() != 32 {
+            return Err(anyhow!("invalid key length: require 32, got {}", key.len()));
+        }
+        let key = GenericArray::from_slice(key);
+        Ok(match cipher {
+            TextCipher::ChaCha20Poly1305 => Self::ChaCha20Poly1305(ChaCha20Poly1305::new(key)),
+            TextCipher::XChaCha20Poly1305 => Self::XChaCha20Poly1305(XChaCha20Poly1305::new(key)),
+            TextCipher::Aes256Gcm => Self::Aes256Gcm(Box::new(Aes256Gcm::new(key))),
+            TextCipher::Aes256GcmSiv => Self::Aes256GcmSiv(Box::new(Aes256GcmSiv::new(key))),
+        })
+    }
+
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            Self::XChaCha20Poly1305(_) => 24,
+            _ => 12,
+        }
+    }
+
+    pub fn encrypt(&self, nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        self.check_nonce(nonce)?;
+        let result = match self {
+            Self::ChaCha20Poly1305(aead) => aead.encrypt(GenericArray::from_slice(nonce), payload),
+            Self::XChaCha20Poly1305(aead) => aead.encrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256Gcm(aead) => aead.encrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256GcmSiv(aead) => aead.encrypt(GenericArray::from_slice(nonce), payload),
+        };
+        result.map_err(|e| anyhow!(e.to_string()))
+    }
+
+    pub fn decrypt(&self, nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        self.check_nonce(nonce)?;
+        let result = match self {
+            Self::ChaCha20Poly1305(aead) => aead.decrypt(GenericArray::from_slice(nonce), payload),
+            Self::XChaCha20Poly1305(aead) => aead.decrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256Gcm(aead) => aead.decrypt(GenericArray::from_slice(nonce), payload),
+            Self::Aes256GcmSiv(aead) => aead.decrypt(GenericArray::from_slice(nonce), payload),
+        };
+        result.map_err(|e| anyhow!(e.to_string()))
+    }
+
+    fn check_nonce(&self, nonce: &[u8]) -> Result<()> {
+        if nonce.len() != self.nonce_size() {
+            return Err(anyhow!(
+                "invalid nonce length: require {}, got {}",
+                self.nonce_size(),
+                nonce.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Random part of the STREAM nonce; the last 5 bytes are the segment
+    /// counter and the last-segment flag.
+    pub fn stream_prefix_size(&self) -> usize {
+        self.nonce_size() - 5
+    }
+}
+
+/// Decrypt what `text encrypt` produces: a container, dispatching on its
+/// header, or the legacy `nonce || ciphertext` of earlier versions.
+pub fn process_text_decrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    secret: Secret,
+    aad: &[u8],
+) -> Result<()> {
+    let mut magic = [0u8; CONTAINER_MAGIC.len()];
+    let n = read_full(reader, &mut magic)?;
+    let mut reader = (&magic[..n]).chain(reader);
+    if &magic == CONTAINER_MAGIC {
+        return process_container_decrypt(&mut reader, writer, secret, aad);
+    }
+    match secret {
+        Secret::Key(key) => {
+            let plaintext = process_text_encrypt(&mut reader, key, aad, false)?;
+            writer.write_all(&plaintext)?;
+            Ok(())
+        }
+        Secret::Passphrase(_) => Err(anyhow!("not passphrase encrypted data")),
+    }
+}
+
+// STREAM-BE32 segment nonce: prefix || big-endian counter || last flag
+fn stream_nonce(prefix: &[u8], counter: u32, last: bool) -> Vec<u8> {
+    let mut nonce = prefix.to_vec();
+    nonce.extend_from_slice(&counter.to_be_bytes());
+    nonce.push(last as u8);
+    nonce
+}
+
+fn next_counter(counter: u32) -> Result<u32> {
+    counter
+        .checked_add(1)
+        .ok_or_else(|| anyhow!("stream too long: segment counter overflow"))
+}
+
+/// The STREAM segments alone, without the nonce prefix in front. `aad` is
+/// authenticated with every segment.
+pub(crate) fn encrypt_segments(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    cipher: &AeadCipher,
+    prefix: &[u8],
+    aad: &[u8],
+) -> Result<()> {
+    let mut counter = 0;
+    // one byte of lookahead tells whether this segment is the last one
+    let mut buf = vec![0u8; STREAM_SEGMENT_SIZE + 1];
+    let mut filled = read_full(reader, &mut buf)?;
+    while filled > STREAM_SEGMENT_SIZE {
+        let msg = &buf[..STREAM_SEGMENT_SIZE];
+        let segment =
+            cipher.encrypt(&stream_nonce(prefix, counter, false), Payload { msg, aad })?;
+        writer.write_all(&segment)?;
+        counter = next_counter(counter)?;
+        buf[0] = buf[STREAM_SEGMENT_SIZE];
+        filled = 1 + read_full(reader, &mut buf[1..])?;
+    }
+    let msg = &buf[..filled];
+    let segment = cipher.encrypt(&stream_nonce(prefix, counter, true), Payload { msg, aad })?;
+    writer.write_all(&segment)?;
+    Ok(())
+}
+
+pub(crate) fn decrypt_segments(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    cipher: &AeadCipher,
+    prefix: &[u8],
+    aad: &[u8],
+) -> Result<()> {
+    let mut counter = 0;
+    let segment_size = STREAM_SEGMENT_SIZE + STREAM_TAG_SIZE;
+    let mut buf = vec![0u8; segment_size + 1];
+    let mut filled = read_full(reader, &mut buf)?;
+    while filled > segment_size {
+        let msg = &buf[..segment_size];
+        let plaintext = cipher
+            .decrypt(&stream_nonce(prefix, counter, false), Payload { msg, aad })
+            .map_err(|_| anyhow!("invalid stream: segment failed to authenticate"))?;
+        writer.write_all(&plaintext)?;
+        counter = next_counter(counter)?;
+        buf[0] = buf[segment_size];
+        filled = 1 + read_full(reader, &mut buf[1..])?;
+    }
+    let msg = &buf[..filled];
+    let plaintext = cipher
+        .decrypt(&stream_nonce(prefix, counter, true), Payload { msg, aad })
+        .map_err(|_| anyhow!("invalid stream: last segment failed to authenticate, truncated?"))?;
+    writer.write_all(&plaintext)?;
+    Ok(())
+}
+
+/// Derive a 32-byte key from `passphrase` with Argon2id.
+pub fn derive_passphrase_key(passphrase: &[u8], salt: &[u8], kdf: &KdfParams) -> Result<[u8; 32]> {
+    if passphrase.is_empty() {
+        return Err(anyhow!("passphrase must not be empty"));
+    }
+    if kdf.m_cost > KDF_MAX_M_COST {
+        return Err(anyhow!(
+            "argon2 memory cost {} KiB is over the {} KiB limit",
+            kdf.m_cost,
+            KDF_MAX_M_COST
+        ));
+    }
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|e| anyhow!(e.to_string()))?;
+    Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::process::container::process_container_encrypt;
 
     const KEY_BLAKE3: &[u8] = include_bytes!("../../fixtures/blake3.txt");
     const PKEY_ED25519: &[u8] = include_bytes!("../../fixtures/ed25519.pk");
@@ -227,9 +791,230 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn t_key_generate() -> Result<()> {
+        let keys = process_text_key_generate(TextKeyMethod::Blake3)?;
+        let key = &keys["blake3.key"];
+        assert_eq!(key.len(), 32);
+        // a printable-only key would never have the high bit set
+        let other = &process_text_key_generate(TextKeyMethod::Blake3)?["blake3.key"];
+        assert!(key.iter().chain(other.iter()).any(|b| *b >= 0x80));
+        assert_ne!(key, other);
+
+        let keys = process_text_key_generate(TextKeyMethod::ChaCha20Poly1305)?;
+        let key = &keys["chacha20poly1305.key"];
+        let plaintext = encrypt_decrypt(b"hello", key)?;
+        assert_eq!(plaintext, b"hello");
+
+        let keys = process_text_key_generate(TextKeyMethod::Ed25519)?;
+        let sig = process_text_sign(
+            &mut &b"hello"[..],
+            &keys["ed25519.sk"],
+            TextSignMethod::Ed25519,
+        )?;
+        assert!(process_text_verify(
+            &mut &b"hello"[..],
+            &keys["ed25519.pk"],
+            &sig,
+            TextSignMethod::Ed25519
+        )?);
+
+        let keys = process_text_key_generate(TextKeyMethod::X25519)?;
+        let identity = String::from_utf8(keys["x25519.key"].clone())?;
+        let recipient = String::from_utf8(keys["x25519.pub"].clone())?;
+        let identities = crate::process::age::parse_identities(&identity)?;
+        assert_eq!(identities[0].recipient().to_string(), recipient.trim());
+        Ok(())
+    }
+
+    #[test]
+    fn t_encode_key() {
+        let key = [0xde, 0xad, 0xbe, 0xef];
+        assert_eq!(encode_key(&key, KeyEncoding::Raw).unwrap(), key);
+        assert_eq!(encode_key(&key, KeyEncoding::Hex).unwrap(), b"deadbeef");
+        assert_eq!(encode_key(&key, KeyEncoding::Base64).unwrap(), b"3q2-7w");
+        assert!(encode_key(&key, KeyEncoding::Pem).is_err());
+    }
+
+    #[test]
+    fn t_load_key() -> Result<()> {
+        let key: [u8; 32] = core::array::from_fn(|i| i as u8 + 0xa0);
+        let hex = hex::encode(key);
+        for encoded in [
+            key.to_vec(),
+            [key.as_slice(), b"\n"].concat(),
+            [key.as_slice(), b"\r\n"].concat(),
+            hex.clone().into_bytes(),
+            format!("  {}\r\n", hex).into_bytes(),
+            URL_SAFE_NO_PAD.encode(key).into_bytes(),
+            format!("{}\n", STANDARD.encode(key)).into_bytes(),
+        ] {
+            assert_eq!(load_key::<32>(&encoded, "test")?, key);
+        }
+        let mut cr = key;
+        cr[31] = b'\r';
+        assert_eq!(
+            load_key::<32>(&[cr.as_slice(), b"\n"].concat(), "test")?,
+            cr
+        );
+
+        let err = |data: &[u8]| load_key::<32>(data, "test").unwrap_err().to_string();
+        assert_eq!(err(b""), "invalid test key: the key is empty");
+        assert_eq!(
+            err(&key[..31]),
+            "invalid test key: require 32 raw bytes, or hex or base64 text, got 31 bytes"
+        );
+        assert_eq!(
+            err(&[key.as_slice(), b"\n\n"].concat()),
+            "invalid test key: require 32 raw bytes, or hex or base64 text, got 34 bytes"
+        );
+        assert_eq!(
+            err(&[key.as_slice(), &key].concat()),
+            "invalid test key: require 32 raw bytes, or hex or base64 text, got 64 bytes"
+        );
+        assert_eq!(
+            err(&hex.as_bytes()[..62]),
+            "invalid test key: hex decodes to 31 bytes, require 32"
+        );
+        assert_eq!(
+            err(&hex.as_bytes()[..63]),
+            "invalid test key: odd number of hex digits (63)"
+        );
+        assert_eq!(
+            err(URL_SAFE_NO_PAD.encode([1u8; 33]).as_bytes()),
+            "invalid test key: base64 decodes to 33 bytes, require 32"
+        );
+        assert_eq!(
+            err(b"not a key, not base64 either!"),
+            "invalid test key: require 32 raw bytes, or hex or base64 text, got 29 bytes"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn t_malformed_keys_and_signatures() -> Result<()> {
+        // short keys used to panic, long ones were silently truncated
+        let short = &SKEY_ED25519[..16];
+        let long = [SKEY_ED25519, b"x"].concat();
+        assert!(Blake3::try_new(short).is_err());
+        assert!(Blake3::try_new([&KEY_BLAKE3[..32], b"x"].concat()).is_err());
+        // one trailing newline is not part of the key, as with hmac keys
+        assert!(Blake3::try_new(KEY_BLAKE3).is_ok());
+        assert!(Ed25519Signer::try_new([SKEY_ED25519, b"\r\n"].concat()).is_ok());
+        assert!(Ed25519Signer::try_new(short).is_err());
+        assert!(Ed25519Signer::try_new(&long).is_err());
+        assert!(Ed25519Verifier::try_new(&PKEY_ED25519[..31]).is_err());
+        assert!(Ed25519Verifier::try_new([PKEY_ED25519, PKEY_ED25519].concat()).is_err());
+
+        // y = 2 is not on the curve
+        let mut off_curve = [0u8; 32];
+        off_curve[0] = 2;
+        let err = Ed25519Verifier::try_new(off_curve).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "invalid ed25519 public key: not a valid curve point"
+        );
+
+        let sig = process_text_sign(&mut &b"hello"[..], SKEY_ED25519, TextSignMethod::Ed25519)?;
+        for method in [TextSignMethod::Ed25519, TextSignMethod::Ed25519ph] {
+            for bad in [&sig[..0], &sig[..63], &[sig.as_slice(), &[0]].concat()] {
+                let err =
+                    process_text_verify(&mut &b"hello"[..], PKEY_ED25519, bad, method).unwrap_err();
+                assert!(err.to_string().starts_with("invalid ed25519 signature"));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn t_blake3_streaming() -> Result<()> {
+        // larger than blake3's internal chunking and than one read
+        let msg: Vec<u8> = (0..=255u8).cycle().take(3 * 1024 * 1024 + 7).collect();
+        let path = std::env::temp_dir().join(format!("rcli-blake3-{}", std::process::id()));
+        std::fs::write(&path, &msg)?;
+
+        let key = &KEY_BLAKE3[..32];
+        let expected = blake3::keyed_hash(key.try_into()?, &msg);
+        let sig = process_text_sign(&mut msg.as_slice(), key, TextSignMethod::Blake3)?;
+        assert_eq!(sig, expected.as_bytes());
+
+        for (mmap, parallel) in [(false, false), (true, false), (false, true)] {
+            let options = HashOptions { mmap, parallel };
+            let ret = process_text_sign_file(&path, key, TextSignMethod::Blake3, options)?;
+            assert_eq!(ret, sig);
+            assert!(process_text_verify_file(
+                &path,
+                key,
+                &sig,
+                TextSignMethod::Blake3,
+                options
+            )?);
+        }
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn t_ed25519ph_rfc8032() -> Result<()> {
+        // RFC 8032 section 7.3, test vector "abc"
+        let sk = hex::decode("833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42")?;
+        let pk = hex::decode("ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf")?;
+        let expected = hex::decode(
+            "98a70222f0b8121aa9d30f813d683f809e462b469c7ff87639499bb94e6dae41\
+             31f85042463c2a355a2003d062adf5aaa10b8c61e636062aaad11c2a26083406",
+        )?;
+
+        let sig = process_text_sign(&mut &b"abc"[..], &sk, TextSignMethod::Ed25519ph)?;
+        assert_eq!(sig, expected);
+        let ok = process_text_verify(&mut &b"abc"[..], &pk, &sig, TextSignMethod::Ed25519ph)?;
+        assert!(ok);
+        // a prehashed signature is not a plain ed25519 one
+        let ok = process_text_verify(&mut &b"abc"[..], &pk, &sig, TextSignMethod::Ed25519)?;
+        assert!(!ok);
+        let ok = process_text_verify(&mut &b"abd"[..], &pk, &sig, TextSignMethod::Ed25519ph)?;
+        assert!(!ok);
+        Ok(())
+    }
+
+    #[test]
+    fn t_hmac_rfc4231() -> Result<()> {
+        // RFC 4231 test case 2
+        let msg = b"what do ya want for nothing?";
+        for (method, expected) in [
+            (
+                TextSignMethod::HmacSha256,
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                TextSignMethod::HmacSha512,
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                 9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+        ] {
+            let sig = process_text_sign(&mut &msg[..], b"Jefe", method)?;
+            assert_eq!(hex::encode(&sig), expected);
+            // the newline `echo Jefe > key` adds is not part of the key
+            assert!(process_text_verify(&mut &msg[..], b"Jefe\n", &sig, method)?);
+            assert!(!process_text_verify(
+                &mut &b"what"[..],
+                b"Jefe",
+                &sig,
+                method
+            )?);
+            assert!(!process_text_verify(
+                &mut &msg[..],
+                b"Jefe",
+                &sig[..16],
+                method
+            )?);
+        }
+        assert!(process_text_sign(&mut &msg[..], b"\n", TextSignMethod::HmacSha256).is_err());
+        Ok(())
+    }
+
     fn encrypt_decrypt(msg: &[u8], key: &[u8]) -> anyhow::Result<Vec<u8>> {
-        let ciphertext = process_text_encrypt(&mut &msg[0..], key, true)?;
-        let plaintext = process_text_encrypt(&mut ciphertext.as_slice(), key, false)?;
+        let ciphertext = process_text_encrypt(&mut &msg[0..], key, &[], true)?;
+        let plaintext = process_text_encrypt(&mut ciphertext.as_slice(), key, &[], false)?;
         Ok(plaintext)
     }
 
@@ -247,4 +1032,129 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn t_encrypt_decrypt_aad() -> Result<()> {
+        let key = b"01234567890123456789012345678901";
+        let ciphertext = process_text_encrypt(&mut &b"record"[..], key, b"invoice-17", true)?;
+        let open = |aad: &[u8]| process_text_encrypt(&mut ciphertext.as_slice(), key, aad, false);
+        assert_eq!(open(b"invoice-17")?, b"record");
+        assert!(open(b"invoice-18").is_err());
+        assert!(open(b"").is_err());
+
+        let mut plaintext = Vec::new();
+        let secret = Secret::Key(key);
+        process_text_decrypt(&mut &ciphertext[..], &mut plaintext, secret, b"invoice-17")?;
+        assert_eq!(plaintext, b"record");
+        Ok(())
+    }
+
+    #[test]
+    fn t_aead_known_answers() -> Result<()> {
+        const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let key: Vec<u8> = (0x80..=0x9f).collect();
+        let aad = hex::decode("50515253c0c1c2c3c4c5c6c7")?;
+        // (cipher, key, nonce, aad, plaintext, ciphertext || tag)
+        let vectors = [
+            // RFC 8439, section 2.8.2
+            (
+                TextCipher::ChaCha20Poly1305,
+                key.clone(),
+                hex::decode("070000004041424344454647")?,
+                aad.clone(),
+                SUNSCREEN.to_vec(),
+                "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b6116\
+                1ae10b594f09e26a7e902ecbd0600691",
+            ),
+            // draft-irtf-cfrg-xchacha-03, appendix A.3.1
+            (
+                TextCipher::XChaCha20Poly1305,
+                key,
+                (0x40..=0x57).collect(),
+                aad,
+                SUNSCREEN.to_vec(),
+                "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b4522f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff921f9664c97637da9768812f615c68b13b52e\
+                c0875924c1c7987947deafd8780acf49",
+            ),
+            // GCM specification, test case 16
+            (
+                TextCipher::Aes256Gcm,
+                hex::decode("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308")?,
+                hex::decode("cafebabefacedbaddecaf888")?,
+                hex::decode("feedfacedeadbeeffeedfacedeadbeefabaddad2")?,
+                hex::decode("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39")?,
+                "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662\
+                76fc6ece0f4e1768cddf8853bb2d551b",
+            ),
+            // RFC 8452, appendix C.2
+            (
+                TextCipher::Aes256GcmSiv,
+                hex::decode("0100000000000000000000000000000000000000000000000000000000000000")?,
+                hex::decode("030000000000000000000000")?,
+                Vec::new(),
+                Vec::new(),
+                "07f5f4169bbf55a8400cd47ea6fd400f",
+            ),
+            (
+                TextCipher::Aes256GcmSiv,
+                hex::decode("0100000000000000000000000000000000000000000000000000000000000000")?,
+                hex::decode("030000000000000000000000")?,
+                Vec::new(),
+                hex::decode("0100000000000000")?,
+                "c2ef328e5c71c83b843122130f7364b761e0b97427e3df28",
+            ),
+        ];
+
+        for (cipher, key, nonce, aad, msg, expected) in vectors {
+            let aead = AeadCipher::try_new(cipher, &key)?;
+            let sealed = aead.encrypt(
+                &nonce,
+                Payload {
+                    msg: &msg,
+                    aad: &aad,
+                },
+            )?;
+            assert_eq!(hex::encode(&sealed), expected, "{}", cipher);
+            let opened = aead.decrypt(
+                &nonce,
+                Payload {
+                    msg: &sealed,
+                    aad: &aad,
+                },
+            )?;
+            assert_eq!(opened, msg);
+            assert!(aead
+                .decrypt(
+                    &nonce,
+                    Payload {
+                        msg: &sealed,
+                        aad: b"x"
+                    }
+                )
+                .is_err());
+        }
+        assert!(AeadCipher::try_new(TextCipher::Aes256Gcm, &[0; 16]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_decrypt_dispatch() -> Result<()> {
+        let msg = b"hello, world";
+        let key = b"01234567890123456789012345678901";
+        let legacy = process_text_encrypt(&mut &msg[..], key, &[], true)?;
+        let mut container = Vec::new();
+        let kdf = KdfParams::default();
+        let secret = Secret::Key(key);
+        let cipher = TextCipher::XChaCha20Poly1305;
+        process_container_encrypt(&mut &msg[..], &mut container, secret, cipher, &kdf, &[])?;
+
+        for ciphertext in [legacy, container] {
+            let mut plaintext = Vec::new();
+            process_text_decrypt(&mut &ciphertext[..], &mut plaintext, Secret::Key(key), &[])?;
+            assert_eq!(plaintext, msg);
+        }
+        let mut sink = Vec::new();
+        assert!(process_text_decrypt(&mut &b"RCLI"[..], &mut sink, Secret::Key(key), &[]).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,249 @@
+use std::io::Read;
+
+use anyhow::{anyhow, Ok, Result};
+
+use super::text::{TextHmac, TextSigner, TextVerifier};
+use crate::cli::webhook::WebhookProvider;
+
+const GITHUB_HEADER: &str = "X-Hub-Signature-256";
+const STRIPE_HEADER: &str = "Stripe-Signature";
+const SLACK_HEADER: &str = "X-Slack-Signature";
+const SLACK_TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+
+impl WebhookProvider {
+    /// The header that carries the signature.
+    pub fn header(&self) -> &'static str {
+        match self {
+            WebhookProvider::Github => GITHUB_HEADER,
+            WebhookProvider::Stripe => STRIPE_HEADER,
+            WebhookProvider::Slack => SLACK_HEADER,
+        }
+    }
+
+    // what is signed besides the body, which always comes last
+    fn signed_prefix(&self, timestamp: u64) -> String {
+        match self {
+            WebhookProvider::Github => String::new(),
+            WebhookProvider::Stripe => format!("{}.", timestamp),
+            WebhookProvider::Slack => format!("v0:{}:", timestamp),
+        }
+    }
+}
+
+/// HMAC-SHA256 of the payload as `provider` lays it out.
+fn webhook_mac(
+    provider: WebhookProvider,
+    secret: &[u8],
+    timestamp: u64,
+    body: &[u8],
+) -> Result<Vec<u8>> {
+    let prefix = provider.signed_prefix(timestamp);
+    let mut payload = prefix.as_bytes().chain(body);
+    TextHmac::sha256(secret)?.sign(&mut payload)
+}
+
+/// Headers a request from `provider` would carry for `body`, as
+/// `(name, value)` pairs.
+pub fn process_webhook_sign(
+    provider: WebhookProvider,
+    secret: &[u8],
+    body: &[u8],
+    timestamp: u64,
+) -> Result<Vec<(&'static str, String)>> {
+    let mac = hex::encode(webhook_mac(provider, secret, timestamp, body)?);
+    let headers = match provider {
+        WebhookProvider::Github => vec![(GITHUB_HEADER, format!("sha256={}", mac))],
+        WebhookProvider::Stripe => {
+            vec![(STRIPE_HEADER, format!("t={},v1={}", timestamp, mac))]
+        }
+        WebhookProvider::Slack => vec![
+            (SLACK_TIMESTAMP_HEADER, timestamp.to_string()),
+            (SLACK_HEADER, format!("v0={}", mac)),
+        ],
+    };
+    Ok(headers)
+}
+
+/// Verify the signature header of a webhook request. Slack sends the
+/// timestamp in a header of its own, pass it as `timestamp`; Stripe's is in
+/// the signature header. Requests signed more than `tolerance` seconds from
+/// `now` are errors, a signature that doesn't match returns false.
+pub fn process_webhook_verify(
+    provider: WebhookProvider,
+    secret: &[u8],
+    body: &[u8],
+    header: &str,
+    timestamp: Option<u64>,
+    tolerance: u64,
+    now: u64,
+) -> Result<bool> {
+    let header = strip_header_name(header, provider.header());
+    let (timestamp, signatures) = match provider {
+        WebhookProvider::Github => {
+            let sig = header
+                .strip_prefix("sha256=")
+                .ok_or_else(|| anyhow!("invalid {} header, expect sha256=<hex>", GITHUB_HEADER))?;
+            (0, vec![sig])
+        }
+        WebhookProvider::Stripe => parse_stripe_header(header)?,
+        WebhookProvider::Slack => {
+            let sig = header
+                .strip_prefix("v0=")
+                .ok_or_else(|| anyhow!("invalid {} header, expect v0=<hex>", SLACK_HEADER))?;
+            let timestamp = timestamp
+                .ok_or_else(|| anyhow!("slack needs the {} header", SLACK_TIMESTAMP_HEADER))?;
+            (timestamp, vec![sig])
+        }
+    };
+    if provider != WebhookProvider::Github && timestamp.abs_diff(now) > tolerance {
+        return Err(anyhow!(
+            "the request timestamp {} is {}s from now, outside the tolerance of {}s",
+            timestamp,
+            timestamp.abs_diff(now),
+            tolerance
+        ));
+    }
+
+    let prefix = provider.signed_prefix(timestamp);
+    let verifier = TextHmac::sha256(secret)?;
+    for sig in signatures {
+        // a signature that isn't hex can't match, but the others still may
+        let Result::Ok(sig) = hex::decode(sig) else {
+            continue;
+        };
+        if verifier.verify(&mut prefix.as_bytes().chain(body), &sig)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// accept the header line as copied from a request dump, `Name: value`
+fn strip_header_name<'a>(header: &'a str, name: &str) -> &'a str {
+    let header = header.trim();
+    match header.split_once(':') {
+        Some((key, value)) if key.trim().eq_ignore_ascii_case(name) => value.trim(),
+        _ => header,
+    }
+}
+
+/// `t=<timestamp>,v1=<hex>[,v1=<hex>...]`; there are several v1 values while
+/// a secret is being rolled, v0 entries are test mode signatures and ignored.
+fn parse_stripe_header(header: &str) -> Result<(u64, Vec<&str>)> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for item in header.split(',') {
+        match item.trim().split_once('=') {
+            Some(("t", t)) => {
+                let t = t
+                    .parse()
+                    .map_err(|_| anyhow!("invalid {} timestamp: {}", STRIPE_HEADER, t))?;
+                timestamp = Some(t);
+            }
+            Some(("v1", sig)) => signatures.push(sig),
+            Some(_) => {}
+            None => return Err(anyhow!("invalid {} header: {}", STRIPE_HEADER, header)),
+        }
+    }
+    let timestamp =
+        timestamp.ok_or_else(|| anyhow!("the {} header has no timestamp", STRIPE_HEADER))?;
+    if signatures.is_empty() {
+        return Err(anyhow!("the {} header has no v1 signature", STRIPE_HEADER));
+    }
+    Ok((timestamp, signatures))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn t_github() -> Result<()> {
+        // the example in GitHub's "Validating webhook deliveries"
+        let secret = b"It's a Secret to Everybody";
+        let body = b"Hello, World!";
+        let expected = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        let headers = process_webhook_sign(WebhookProvider::Github, secret, body, NOW)?;
+        assert_eq!(headers, vec![(GITHUB_HEADER, expected.to_string())]);
+
+        let verify = |header: &str, body: &[u8]| {
+            process_webhook_verify(WebhookProvider::Github, secret, body, header, None, 0, NOW)
+        };
+        assert!(verify(expected, body)?);
+        assert!(verify(&format!("x-hub-signature-256: {}", expected), body)?);
+        assert!(!verify(expected, b"Hello, World?")?);
+        assert!(verify(&expected[7..], body).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn t_slack() -> Result<()> {
+        // the example in Slack's "Verifying requests from Slack"
+        let secret = b"8f742231b10e8888abcd99yyyzzz85a5";
+        let body = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow\
+                    &channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner\
+                    &command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2F\
+                    commands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN\
+                    &trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+        let timestamp = 1531420618;
+        let expected = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+        let headers =
+            process_webhook_sign(WebhookProvider::Slack, secret, body.as_bytes(), timestamp)?;
+        assert_eq!(headers[1], (SLACK_HEADER, expected.to_string()));
+
+        let verify = |timestamp, now| {
+            let body = body.as_bytes();
+            process_webhook_verify(
+                WebhookProvider::Slack,
+                secret,
+                body,
+                expected,
+                timestamp,
+                300,
+                now,
+            )
+        };
+        assert!(verify(Some(timestamp), timestamp + 300)?);
+        assert!(verify(Some(timestamp), timestamp + 301).is_err());
+        assert!(verify(None, timestamp).is_err());
+        // the timestamp is signed too
+        assert!(!verify(Some(timestamp + 1), timestamp)?);
+        Ok(())
+    }
+
+    #[test]
+    fn t_stripe() -> Result<()> {
+        let secret = b"whsec_test";
+        let body = br#"{"id":"evt_1","object":"event"}"#;
+        // HMAC-SHA256 of "{t}.{body}", computed with openssl dgst -hmac
+        let expected = "t=1700000000,\
+                        v1=4c15fb2a43f93ef61eaa3e0893866d57cad9cad4223242cfb96f362480cb4021";
+        let headers = process_webhook_sign(WebhookProvider::Stripe, secret, body, NOW)?;
+        assert_eq!(headers, vec![(STRIPE_HEADER, expected.to_string())]);
+        let (_, header) = &headers[0];
+
+        let verify = |header: &str| {
+            process_webhook_verify(
+                WebhookProvider::Stripe,
+                secret,
+                body,
+                header,
+                None,
+                300,
+                NOW,
+            )
+        };
+        assert!(verify(header)?);
+        // any of several v1 signatures may match, v0 is ignored
+        let (_, sig) = header.split_once(",v1=").unwrap();
+        let rolled = format!("t={},v1={},v1={},v0={}", NOW, "00".repeat(32), sig, sig);
+        assert!(verify(&rolled)?);
+        assert!(!verify(&format!("t={},v1={}", NOW, "00".repeat(32)))?);
+        assert!(verify(&format!("t={},v0={}", NOW, sig)).is_err());
+        assert!(verify(&format!("v1={}", sig)).is_err());
+        assert!(verify(&format!("t={},v1={}", NOW - 301, sig)).is_err());
+        Ok(())
+    }
+}
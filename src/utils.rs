@@ -1,6 +1,12 @@
-use std::{fs, io};
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Ok;
+use anyhow::{anyhow, Ok};
 
 pub fn open_reader(infile: &str) -> anyhow::Result<Box<dyn io::Read>> {
     if infile == "-" {
@@ -10,9 +16,95 @@ pub fn open_reader(infile: &str) -> anyhow::Result<Box<dyn io::Read>> {
     }
 }
 
+pub fn open_writer(outfile: &str) -> anyhow::Result<Box<dyn io::Write>> {
+    if outfile == "-" {
+        Ok(Box::new(io::stdout()) as Box<dyn io::Write>)
+    } else {
+        let file = fs::File::create(outfile)?;
+        Ok(Box::new(io::BufWriter::new(file)) as Box<dyn io::Write>)
+    }
+}
+
+/// `data` without one trailing newline, `\n` or `\r\n`, as `echo` and
+/// editors leave at the end of key and passphrase files.
+pub fn trim_newline(data: &[u8]) -> &[u8] {
+    match data.strip_suffix(b"\n") {
+        Some(data) => data.strip_suffix(b"\r").unwrap_or(data),
+        None => data,
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn current_timestamp() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Fill `buf` unless EOF comes first, returning how much was read. Lets
+/// block based formats work on aligned chunks whatever the reader returns.
+pub fn read_full(input: &mut dyn io::Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match input.read(&mut buf[n..])? {
+            0 => break,
+            m => n += m,
+        }
+    }
+    io::Result::Ok(n)
+}
+
 pub fn read_content(infile: &str) -> anyhow::Result<Vec<u8>> {
     let mut reader = open_reader(infile)?;
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
     Ok(buf)
 }
+
+/// Write `path` through `write` into a temporary file next to it, renamed
+/// over `path` only once `write` succeeds. A failure leaves no partial
+/// output and whatever was at `path` before untouched.
+pub fn write_file_replacing(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid output file: {}", path.display()))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)?;
+    let result = (|| {
+        let mut writer = io::BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Write key material readable by the owner only (0600 on unix).
+pub fn write_private_file(path: impl AsRef<Path>, contents: &[u8]) -> anyhow::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // mode only applies on creation, tighten an existing file as well
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)?;
+    Ok(())
+}